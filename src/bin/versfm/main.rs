@@ -1,61 +1,60 @@
 use clap::Parser;
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode, KeyEvent},
-    terminal::enable_raw_mode,
+    event::{DisableMouseCapture, Event as CEvent, EventStream, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, LeaveAlternateScreen},
 };
+use futures::StreamExt;
 use rusoto_core::Region;
 use std::{error::Error, process, str::FromStr};
 use std::{
+    fs::OpenOptions,
     io::{self, Stdout},
-    sync::mpsc::{self, Receiver},
-    time::{Duration, Instant},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    Terminal,
 };
-use tui::{backend::CrosstermBackend, Terminal};
 use versfm::{
-    components::{FileCRUDListWidget, FilesystemList, S3List},
-    providers::s3::S3Provider,
-    screens::DualPaneList,
+    components::{FileCRUDListWidget, ProviderList},
+    providers::{
+        cache::{CachedProvider, ListingCache, DEFAULT_TTL},
+        filesystem::{FilesystemProvider, DEFAULT_CHUNK_SIZE},
+        plugin::PluginProvider,
+        retry::{RetryPolicy, DEFAULT_BASE_DELAY, DEFAULT_MAX_RETRIES},
+        s3::{S3Provider, DEFAULT_PART_SIZE},
+        LinkPolicy,
+    },
+    screens::{Action, DualPaneList, Event},
+    transfer::{ConflictPolicy, TransferOptions, DEFAULT_MAX_JOBS, DEFAULT_MAX_JOBS_PER_PROVIDER},
 };
 
-enum Event<I> {
-    Input(I),
-    Shutdown,
-    Tick,
-}
-
-fn spawn_sender() -> Receiver<Event<KeyEvent>> {
-    let (tx, rx) = mpsc::channel();
-    let tick_rate = Duration::from_millis(75);
-
+/// Spawns a task forwarding terminal events onto the given channel
+fn spawn_sender(tx: UnboundedSender<Event<Action>>) {
     tokio::spawn(async move {
-        let mut last_tick = Instant::now();
-
-        loop {
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            if event::poll(timeout).expect("Timeout ocurred while polling event") {
-                if let CEvent::Key(key) = event::read().expect("Couldn't read key") {
-                    if key.code == KeyCode::Esc {
-                        tx.send(Event::Shutdown)
-                            .expect("Couldn't send shutdown event");
-                        break;
-                    } else {
-                        tx.send(Event::Input(key))
-                            .expect("Couldn't send user input event");
-                    }
-                }
-            }
+        let mut terminal_events = EventStream::new();
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
+        while let Some(event) = terminal_events.next().await {
+            let event = match event.expect("Couldn't read terminal event") {
+                CEvent::Key(key) if key.code == KeyCode::Esc => Event::Shutdown,
+                CEvent::Key(key) => match Action::from_key_event(&key) {
+                    Some(action) => Event::Input(action),
+                    None => continue,
+                },
+                CEvent::Resize(_, _) => Event::StateChanged,
+                CEvent::Mouse(_) => continue,
+            };
+            let shutdown = matches!(event, Event::Shutdown);
+            if tx.send(event).is_err() || shutdown {
+                break;
             }
         }
     });
-    rx
 }
 
 fn capture_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, Box<dyn Error>> {
@@ -67,7 +66,26 @@ fn capture_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, Box<dyn Erro
     Ok(terminal)
 }
 
-async fn get_pane(pane_str: &str) -> Box<dyn FileCRUDListWidget> {
+/// Restores the terminal to the state it was in before it was captured
+fn release_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+) -> Result<(), Box<dyn Error>> {
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+    terminal.clear()?;
+    Ok(())
+}
+
+async fn get_pane(
+    pane_str: &str,
+    cache: &ListingCache,
+    retry: RetryPolicy,
+) -> Box<dyn FileCRUDListWidget> {
     match pane_str {
         "s3" => {
             let s3_args = Args::parse();
@@ -76,15 +94,50 @@ async fn get_pane(pane_str: &str) -> Box<dyn FileCRUDListWidget> {
                 process::exit(1);
             }
             if let Ok(region) = Region::from_str(&s3_args.aws_region.unwrap()) {
-                Box::new(S3List::new(
-                    S3Provider::new(&s3_args.s3_bucket_name.unwrap(), region).await,
-                ))
+                match S3Provider::new(&s3_args.s3_bucket_name.unwrap(), region).await {
+                    Ok(provider) => Box::new(
+                        ProviderList::new(CachedProvider::new(
+                            provider.with_part_size(s3_args.s3_part_size),
+                            cache.clone(),
+                        ))
+                        .with_retry_policy(retry),
+                    ),
+                    Err(e) => {
+                        println!("Error: {}", e.message());
+                        process::exit(1);
+                    }
+                }
             } else {
                 println!("Error: Provided AWS region is incorrect");
                 process::exit(1);
             }
         }
-        "fs" => Box::new(FilesystemList::new()),
+        "plugin" => {
+            let command = Args::parse().plugin_command.unwrap_or_else(|| {
+                println!("Error: Please provide the command used to start the plugin");
+                process::exit(1);
+            });
+            let mut command = command.split_whitespace();
+            let program = command.next().unwrap_or_default();
+            let args: Vec<&str> = command.collect();
+            match PluginProvider::new(program, &args).await {
+                Ok(provider) => Box::new(
+                    ProviderList::new(CachedProvider::new(provider, cache.clone()))
+                        .with_retry_policy(retry),
+                ),
+                Err(e) => {
+                    println!("Error: {}", e.message());
+                    process::exit(1);
+                }
+            }
+        }
+        "fs" => Box::new(
+            ProviderList::new(CachedProvider::new(
+                FilesystemProvider::with_chunk_size(Args::parse().chunk_size),
+                cache.clone(),
+            ))
+            .with_retry_policy(retry),
+        ),
         _ => {
             println!("Error: Please provide a valid provider");
             process::exit(1);
@@ -92,26 +145,81 @@ async fn get_pane(pane_str: &str) -> Box<dyn FileCRUDListWidget> {
     }
 }
 
+/// Sets up logging to the file given in the arguments, nothing is
+/// logged if no file is given
+fn init_logging(args: &Args) -> Result<(), Box<dyn Error>> {
+    let path = match &args.log_file {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let filter = EnvFilter::try_new(&args.log_level)?;
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+    Ok(())
+}
+
 pub async fn run() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let left_pane = get_pane(&args.left_pane).await;
-    let right_pane = get_pane(&args.right_pane).await;
+    if let Err(e) = init_logging(&args) {
+        println!("Error: Couldn't set up logging: {}", e);
+        process::exit(1);
+    }
+    let cache = ListingCache::new(Duration::from_secs(args.cache_ttl));
+    let retry = RetryPolicy {
+        max_retries: args.retries,
+        base_delay: Duration::from_millis(args.retry_delay),
+        ..Default::default()
+    };
+    let left_pane = get_pane(&args.left_pane, &cache, retry).await;
+    let right_pane = get_pane(&args.right_pane, &cache, retry).await;
 
     let terminal = capture_terminal().expect("Couldn't capture terminal");
-    let mut main_screen = DualPaneList::new(terminal, left_pane, right_pane).await;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let options = TransferOptions {
+        link_policy: args.links,
+        max_jobs: args.max_jobs,
+        max_jobs_per_provider: args.max_jobs_per_provider,
+        retry,
+        conflict_policy: args.on_conflict,
+        verify_checksums: !args.no_verify,
+    };
+    let mut main_screen =
+        DualPaneList::new(terminal, left_pane, right_pane, options, tx.clone()).await;
 
-    let input_channel = spawn_sender();
-    loop {
-        match input_channel.recv().unwrap() {
-            Event::Input(event) => main_screen.handle_event(event).await,
-            Event::Shutdown => {
-                main_screen.shutdown()?;
-                break;
+    spawn_sender(tx);
+    event_loop(&mut main_screen, rx).await?;
+    release_terminal(main_screen.terminal_mut())
+}
+
+/// Handles events until a shutdown is requested, and then waits for the
+/// cancelled jobs to stop
+///
+/// All of the events already waiting in the channel are handled before
+/// the screen is redrawn, and nothing is redrawn while no events arrive.
+async fn event_loop<B: Backend>(
+    screen: &mut DualPaneList<B>,
+    mut rx: UnboundedReceiver<Event<Action>>,
+) -> Result<(), Box<dyn Error>> {
+    screen.render()?;
+    while let Some(event) = rx.recv().await {
+        let mut next = Some(event);
+        while let Some(event) = next {
+            match event {
+                Event::Input(action) => screen.handle_event(action).await,
+                Event::StateChanged => (),
+                Event::Shutdown => {
+                    screen.shutdown().await;
+                    return Ok(());
+                }
             }
-            Event::Tick => main_screen
-                .render()
-                .expect("Couldn't render DualPaneList screen"),
+            next = rx.try_recv().ok();
         }
+        screen.render()?;
     }
     Ok(())
 }
@@ -120,10 +228,10 @@ pub async fn run() -> Result<(), Box<dyn Error>> {
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about=None)]
 struct Args {
-    /// Provider for the left pane [Options: "fs", "s3"]
+    /// Provider for the left pane [Options: "fs", "s3", "plugin"]
     #[clap(long, short, default_value = "fs")]
     left_pane: String,
-    /// Provider for the right pane [Options: "fs", "s3"]
+    /// Provider for the right pane [Options: "fs", "s3", "plugin"]
     #[clap(long, short, default_value = "fs")]
     right_pane: String,
     /// Name of the aws region your bucket is located in
@@ -132,6 +240,56 @@ struct Args {
     /// Name of the bucket you want to connect to
     #[clap(long)]
     s3_bucket_name: Option<String>,
+    /// Size (in bytes) of the parts files of unknown size are uploaded
+    /// to S3 in (at least 5 MiB)
+    #[clap(long, default_value_t = DEFAULT_PART_SIZE)]
+    s3_part_size: usize,
+    /// Command (program followed by its arguments) starting the plugin
+    /// backing the "plugin" pane
+    #[clap(long)]
+    plugin_command: Option<String>,
+    /// How symbolic links should be copied [Options: "follow", "recreate", "skip"]
+    #[clap(long, default_value = "follow")]
+    links: LinkPolicy,
+    /// What to do with files already present in the destination of a copy
+    /// or move [Options: "ask", "overwrite", "skip", "rename",
+    /// "if-newer", "if-different"]. "if-different" compares the sizes and
+    /// checksums of the files, or only their sizes with --no-verify
+    #[clap(long, default_value = "ask")]
+    on_conflict: ConflictPolicy,
+    /// Don't compute the checksums of transferred files, nor compare them
+    /// with the checksums of the written files
+    #[clap(long)]
+    no_verify: bool,
+    /// Maximum number of files transferred or deleted at the same time
+    #[clap(long, default_value_t = DEFAULT_MAX_JOBS)]
+    max_jobs: usize,
+    /// Maximum number of files transferred to, from, or deleted in the
+    /// same bucket or filesystem at the same time
+    #[clap(long, default_value_t = DEFAULT_MAX_JOBS_PER_PROVIDER)]
+    max_jobs_per_provider: usize,
+    /// Number of times operations failed with transient errors (eg.
+    /// network errors or throttling) are retried, 0 disables retrying
+    #[clap(long, default_value_t = DEFAULT_MAX_RETRIES)]
+    retries: u32,
+    /// Delay (in milliseconds) before the first retry, doubled with
+    /// every following one
+    #[clap(long, default_value_t = DEFAULT_BASE_DELAY.as_millis() as u64)]
+    retry_delay: u64,
+    /// Size (in bytes) of the chunks local files are read and written in
+    #[clap(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+    chunk_size: usize,
+    /// Time (in seconds) after which cached directory listings expire,
+    /// 0 disables caching
+    #[clap(long, default_value_t = DEFAULT_TTL.as_secs())]
+    cache_ttl: u64,
+    /// File the log is appended to, nothing is logged if it isn't given
+    #[clap(long, env = "VERSFM_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// Minimum level of logged events ["error", "warn", "info", "debug",
+    /// "trace"], or a list of filtering directives (eg. "info,versfm::providers=debug")
+    #[clap(long, env = "VERSFM_LOG", default_value = "info")]
+    log_level: String,
 }
 
 #[tokio::main]
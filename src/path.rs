@@ -0,0 +1,202 @@
+//! Module defining the path type used to address entries exposed
+//! by providers
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::{self},
+    str::FromStr,
+};
+
+/// Path of an entry exposed by a provider
+///
+/// It consists of a scheme identifying the provider (eg. `s3`, `file`),
+/// a root identifying the resource the provider operates on (eg. the
+/// name of an S3 bucket) and a list of path segments. Segments are kept
+/// verbatim as `OsString`s, so they can be empty, contain characters that
+/// would otherwise have special meaning in a string path, or not be valid
+/// UTF-8 at all. When displayed, invalid UTF-8 sequences are replaced
+/// with `U+FFFD`.
+///
+/// # Examples
+///
+/// ```
+/// use versfm::path::VirtualPath;
+/// let path: VirtualPath = "s3://bucket/a/b".parse().unwrap();
+/// assert_eq!(path.scheme(), "s3");
+/// assert_eq!(path.root(), "bucket");
+/// assert_eq!(path.segments(), ["a", "b"]);
+/// assert_eq!(path.to_string(), "s3://bucket/a/b");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VirtualPath {
+    scheme: String,
+    root: String,
+    segments: Vec<OsString>,
+}
+
+/// Error returned when a string cannot be parsed into a `VirtualPath`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePathError(String);
+
+impl fmt::Display for ParsePathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ParsePathError: {{ Path: {} }}", &self.0)
+    }
+}
+
+impl std::error::Error for ParsePathError {}
+
+impl VirtualPath {
+    /// Creates a path pointing to the root of the given resource
+    ///
+    /// # Arguments
+    ///
+    /// * `scheme` - Scheme identifying the provider
+    /// * `root` - Name of the resource the provider operates on
+    pub fn new(scheme: &str, root: &str) -> VirtualPath {
+        VirtualPath {
+            scheme: scheme.to_owned(),
+            root: root.to_owned(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Creates a path out of the given segments
+    ///
+    /// # Arguments
+    ///
+    /// * `scheme` - Scheme identifying the provider
+    /// * `root` - Name of the resource the provider operates on
+    /// * `segments` - Segments of the path, relative to the root
+    pub fn from_segments(scheme: &str, root: &str, segments: Vec<OsString>) -> VirtualPath {
+        VirtualPath {
+            scheme: scheme.to_owned(),
+            root: root.to_owned(),
+            segments,
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn segments(&self) -> &[OsString] {
+        &self.segments
+    }
+
+    /// Returns `true` if the path points to the root of the resource
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the last segment of the path, or `None` if the path
+    /// points to the root of the resource
+    pub fn file_name(&self) -> Option<&OsStr> {
+        self.segments.last().map(|s| s.as_os_str())
+    }
+
+    /// Returns the path of the parent directory, or `None` if the path
+    /// points to the root of the resource
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::path::VirtualPath;
+    /// let path: VirtualPath = "file:///home/user".parse().unwrap();
+    /// assert_eq!(path.parent().unwrap().to_string(), "file:///home");
+    /// assert!(VirtualPath::new("file", "").parent().is_none());
+    /// ```
+    pub fn parent(&self) -> Option<VirtualPath> {
+        if self.is_root() {
+            return None;
+        }
+        let mut parent = self.clone();
+        parent.segments.pop();
+        Some(parent)
+    }
+
+    /// Appends given name as a new segment at the end of the path
+    ///
+    /// The name is stored verbatim, it is not split on '/' characters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::path::VirtualPath;
+    /// let dir: VirtualPath = "s3://bucket/a".parse().unwrap();
+    /// let file = dir.join("some file.txt");
+    /// assert_eq!(file.segments(), ["a", "some file.txt"]);
+    /// assert_eq!(file.parent().unwrap(), dir);
+    /// ```
+    pub fn join(&self, name: impl AsRef<OsStr>) -> VirtualPath {
+        let mut path = self.clone();
+        path.segments.push(name.as_ref().to_owned());
+        path
+    }
+
+    /// Returns the part of the path following the root, with each
+    /// segment preceded by the '/' character
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::path::VirtualPath;
+    /// let path: VirtualPath = "s3://bucket/a/b".parse().unwrap();
+    /// assert_eq!(path.path_str(), "/a/b");
+    /// assert_eq!(VirtualPath::new("s3", "bucket").path_str(), "/");
+    /// ```
+    pub fn path_str(&self) -> String {
+        if self.is_root() {
+            return String::from("/");
+        }
+        self.segments.iter().fold(String::new(), |mut out, s| {
+            out.push('/');
+            out.push_str(&s.to_string_lossy());
+            out
+        })
+    }
+}
+
+impl fmt::Display for VirtualPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", &self.scheme, &self.root)?;
+        for segment in &self.segments {
+            write!(f, "/{}", segment.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VirtualPath {
+    type Err = ParsePathError;
+
+    /// Parses a path in the `scheme://root/segment/segment` format
+    ///
+    /// Every '/' character following the root starts a new segment,
+    /// so empty segments are preserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::path::VirtualPath;
+    /// let path: VirtualPath = "s3://bucket//a".parse().unwrap();
+    /// assert_eq!(path.segments(), ["", "a"]);
+    /// assert!("no-scheme".parse::<VirtualPath>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| ParsePathError(s.to_owned()))?;
+        if scheme.is_empty() {
+            return Err(ParsePathError(s.to_owned()));
+        }
+        let (root, segments) = match rest.split_once('/') {
+            None => (rest, Vec::new()),
+            Some((root, segments)) => (root, segments.split('/').map(OsString::from).collect()),
+        };
+        Ok(VirtualPath::from_segments(scheme, root, segments))
+    }
+}
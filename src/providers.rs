@@ -1,24 +1,293 @@
 //! Module defining providers used for integrating with various
 //! filesystems, object stores, etc.
-use std::io;
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    pin::Pin,
+    str::FromStr,
+};
 
+use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use futures::Stream;
 
+pub mod cache;
+pub mod err;
 pub mod filesystem;
+pub mod plugin;
+pub mod retry;
 pub mod s3;
 
+use self::err::ProviderError;
+use crate::path::VirtualPath;
+
 /// Enum representing the possible kinds of files
 ///
 /// * `File` - Regular File
 /// * `Directory` - Directory File
+/// * `Symlink` - Symbolic link pointing to `target`, `is_dir` signifies
+///   whether the target is a directory and `broken` whether the target
+///   doesn't exist
+/// * `Fifo` - Named pipe
+/// * `Socket` - Unix domain socket
+/// * `BlockDevice` - Block device file
+/// * `CharDevice` - Character device file
 /// * `Unknown` - File of unknown type (possibly a result
-/// of the program not being able to read its metadata)
-#[derive(Clone)]
+///   of the program not being able to read its metadata)
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Kind {
     File,
     Directory,
+    Symlink {
+        target: OsString,
+        is_dir: bool,
+        broken: bool,
+    },
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
     Unknown,
 }
 
+impl Kind {
+    /// Returns `true` if entries of this kind can be navigated into
+    pub fn is_navigable(&self) -> bool {
+        matches!(self, Kind::Directory | Kind::Symlink { is_dir: true, .. })
+    }
+
+    /// Returns `true` if the entry is a FIFO, socket or a device file
+    pub fn is_special(&self) -> bool {
+        matches!(
+            self,
+            Kind::Fifo | Kind::Socket | Kind::BlockDevice | Kind::CharDevice
+        )
+    }
+}
+
+/// Enum representing how symbolic links should be treated when they
+/// are copied or moved
+///
+/// * `Follow` - Contents of the link's target are transferred
+/// * `Recreate` - A link pointing to the same target is created
+///   in the destination
+/// * `Skip` - Links are not transferred
+///
+/// FIFOs, sockets and device files are never transferred, regardless
+/// of the policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinkPolicy {
+    #[default]
+    Follow,
+    Recreate,
+    Skip,
+}
+
+impl FromStr for LinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(LinkPolicy::Follow),
+            "recreate" => Ok(LinkPolicy::Recreate),
+            "skip" => Ok(LinkPolicy::Skip),
+            _ => Err(format!(
+                "Invalid link policy '{}' [Options: \"follow\", \"recreate\", \"skip\"]",
+                s
+            )),
+        }
+    }
+}
+
+/// Struct containing provider-neutral metadata of an entry
+///
+/// Fields the provider couldn't obtain, or that are not applicable
+/// to it, are set to `None`
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    /// Size of the file in bytes
+    pub size: Option<u64>,
+    /// Time of the last modification of the file
+    pub modified: Option<DateTime<Utc>>,
+    /// Unix permission bits of the file
+    pub permissions: Option<u32>,
+    /// Name or identifier of the owner of the file
+    pub owner: Option<String>,
+    /// Entity tag of the object (eg. the S3 ETag)
+    pub etag: Option<String>,
+    /// Base64-encoded SHA-256 checksum of the file contents (never
+    /// reported by S3, see the `s3` module)
+    pub sha256: Option<String>,
+    /// Base64-encoded CRC32C checksum (in big-endian byte order) of the
+    /// file contents (never reported by S3, see the `s3` module)
+    pub crc32c: Option<String>,
+    /// Storage class of the object (eg. `STANDARD`, `GLACIER`)
+    pub storage_class: Option<String>,
+    /// MIME type of the file contents
+    pub content_type: Option<String>,
+    /// Whether the object is encrypted with a key of its own (eg. S3
+    /// SSE-KMS or SSE-C), in which case its checksums can't be obtained
+    pub encrypted_with_key: Option<bool>,
+}
+
+/// Struct describing the optional operations and features a provider
+/// supports
+///
+/// Listing, reading, writing and deleting files is supported by every
+/// provider, so it isn't described here.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Entries can be grouped into directories that can be navigated
+    pub directories: bool,
+    /// Directories can be created (`create_dir`)
+    pub create_dir: bool,
+    /// Files can be renamed (`rename`)
+    pub rename: bool,
+    /// Symbolic links can be created (`create_symlink`)
+    pub symlinks: bool,
+    /// Files can be copied within the provider without downloading them
+    pub server_side_copy: bool,
+    /// Parts of files can be read without reading the whole file
+    pub range_reads: bool,
+    /// Metadata of files can be modified
+    pub metadata_edit: bool,
+    /// Previous versions of files are kept and can be accessed
+    pub versioning: bool,
+    /// Size of the parts large files are written in, if they are written
+    /// in multiple parts whose digests make up their ETag (eg. S3
+    /// multipart uploads)
+    pub multipart_part_size: Option<u64>,
+    /// Maximum number of parts a file can be written in, larger files of
+    /// known size are written in proportionally larger parts
+    pub multipart_max_parts: Option<u64>,
+}
+
+impl Capabilities {
+    /// Returns the size of the parts a file of given size is written in,
+    /// if files are written in multiple parts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::providers::Capabilities;
+    /// let capabilities = Capabilities {
+    ///     multipart_part_size: Some(100),
+    ///     multipart_max_parts: Some(10),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(capabilities.part_size(None), Some(100));
+    /// assert_eq!(capabilities.part_size(Some(1000)), Some(100));
+    /// assert_eq!(capabilities.part_size(Some(1001)), Some(101));
+    /// ```
+    pub fn part_size(&self, size: Option<u64>) -> Option<u64> {
+        let part_size = self.multipart_part_size?;
+        match size.zip(self.multipart_max_parts) {
+            Some((size, max_parts)) if max_parts > 0 => {
+                Some(part_size.max(size.div_ceil(max_parts)))
+            }
+            _ => Some(part_size),
+        }
+    }
+}
+
+/// Struct representing an entry returned by a provider
+///
+/// `name` is the last segment of the entry's path
+#[derive(Clone)]
+pub struct Object {
+    pub name: OsString,
+    pub kind: Kind,
+    pub metadata: Metadata,
+}
+
 pub type BoxedByteStream = Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send + 'static>;
+
+/// Common interface of all the data stores versfm can operate on
+///
+/// Entries are addressed with `VirtualPath`s, it is up to the provider
+/// to map their segments onto its own addressing scheme.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Returns the name of the provider
+    ///
+    /// eg. 'S3', 'local', 'onedrive', etc.
+    fn provider_name(&self) -> &str;
+    /// Returns the name of the resource the provider operates on
+    ///
+    /// eg. name of the S3 bucket
+    fn resource_name(&self) -> &str;
+    /// Returns the optional operations and features the provider supports
+    fn capabilities(&self) -> Capabilities;
+    /// Returns the path of the directory that should be displayed
+    /// when the provider is opened
+    fn initial_path(&self) -> VirtualPath;
+    /// Lists the entries present in the directory under given `path`
+    async fn list(&self, path: &VirtualPath) -> Result<Vec<Object>, ProviderError>;
+    /// Obtains information about the entry under given `path`
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError>;
+    /// Obtains the byte stream of the file under given `path`
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError>;
+    /// Obtains the byte stream of at most `length` bytes of the file
+    /// under given `path`, starting at `offset`. If `length` is `None`
+    /// the stream ends at the end of the file
+    ///
+    /// Providers that do not support this operation can leave
+    /// the default implementation
+    async fn read_range(
+        &self,
+        _path: &VirtualPath,
+        _offset: u64,
+        _length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        Err(ProviderError::unsupported("read_range"))
+    }
+    /// Saves the contents of the given `stream` under given `path`
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError>;
+    /// Deletes the file under given `path`
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError>;
+    /// Renames the file under `from` to `to`
+    ///
+    /// Providers that do not support this operation can leave
+    /// the default implementation
+    async fn rename(&self, _from: &VirtualPath, _to: &VirtualPath) -> Result<(), ProviderError> {
+        Err(ProviderError::unsupported("rename"))
+    }
+    /// Creates a symbolic link under given `path`, pointing to `target`
+    ///
+    /// Providers that do not support this operation can leave
+    /// the default implementation
+    async fn create_symlink(
+        &self,
+        _path: &VirtualPath,
+        _target: &OsStr,
+    ) -> Result<(), ProviderError> {
+        Err(ProviderError::unsupported("create_symlink"))
+    }
+    /// Creates a directory under given `path`
+    ///
+    /// Providers that do not support this operation can leave
+    /// the default implementation
+    async fn create_dir(&self, _path: &VirtualPath) -> Result<(), ProviderError> {
+        Err(ProviderError::unsupported("create_dir"))
+    }
+    /// Returns the path the file under given `path` is stored under, with
+    /// any aliases (eg. symbolic links) resolved, so paths of the same file
+    /// can be told apart from paths of different files
+    ///
+    /// Providers addressing every file with a single path can leave the
+    /// default implementation
+    async fn canonicalize(&self, path: &VirtualPath) -> Result<VirtualPath, ProviderError> {
+        Ok(path.clone())
+    }
+    /// Drops the listing of given `path` from the provider's cache, so
+    /// the next `list` fetches it anew
+    ///
+    /// Providers that don't cache listings can leave the default
+    /// implementation
+    fn invalidate(&self, _path: &VirtualPath) {}
+}
@@ -0,0 +1,250 @@
+//! Module containing the cache of directory listings shared by providers
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tracing::debug;
+
+use super::{err::ProviderError, BoxedByteStream, Capabilities, Object, Provider};
+use crate::path::VirtualPath;
+
+/// Default time after which cached listings expire
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Key identifying a listing: name of the provider, name of the resource
+/// and the path of the listed directory
+type Key = (String, String, VirtualPath);
+
+struct Listing {
+    fetched: Instant,
+    objects: Vec<Object>,
+}
+
+/// Cached listings, along with the number of times each of them has
+/// been invalidated
+#[derive(Default)]
+struct Listings {
+    listings: HashMap<Key, Listing>,
+    generations: HashMap<Key, u64>,
+}
+
+/// Cache of directory listings, keyed by provider and path
+///
+/// Clones of the cache share the same listings, so providers wrapped
+/// with the same cache (eg. the providers of both panes) reuse each
+/// other's listings.
+#[derive(Clone)]
+pub struct ListingCache {
+    ttl: Duration,
+    listings: Arc<Mutex<Listings>>,
+}
+
+impl ListingCache {
+    /// Creates an empty cache, whose listings expire after `ttl`
+    pub fn new(ttl: Duration) -> ListingCache {
+        ListingCache {
+            ttl,
+            listings: Arc::new(Mutex::new(Listings::default())),
+        }
+    }
+
+    fn lock_listings(&self) -> MutexGuard<'_, Listings> {
+        self.listings.lock().expect("Couldn't lock listings mutex")
+    }
+
+    /// Returns the cached listing under given key, if it hasn't expired
+    fn get(&self, key: &Key) -> Option<Vec<Object>> {
+        let listings = &mut self.lock_listings().listings;
+        match listings.get(key) {
+            Some(listing) if listing.fetched.elapsed() < self.ttl => Some(listing.objects.clone()),
+            Some(_) => {
+                listings.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the generation of the listing under given key, ie. the
+    /// number of times it has been invalidated
+    fn generation(&self, key: &Key) -> u64 {
+        self.lock_listings()
+            .generations
+            .get(key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Caches given listing fetched in the given generation, dropping all
+    /// of the expired ones
+    ///
+    /// Listings fetched before the latest invalidation of their key may
+    /// miss the changes it was made for, so they aren't cached.
+    fn insert(&self, key: Key, objects: Vec<Object>, generation: u64) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut listings = self.lock_listings();
+        if listings.generations.get(&key).copied().unwrap_or(0) != generation {
+            debug!(path = %key.2, "Listing changed while it was fetched, not cached");
+            return;
+        }
+        listings
+            .listings
+            .retain(|_, l| l.fetched.elapsed() < self.ttl);
+        listings.listings.insert(
+            key,
+            Listing {
+                fetched: Instant::now(),
+                objects,
+            },
+        );
+    }
+
+    /// Drops the cached listing under given key, and moves it to the
+    /// next generation
+    fn remove(&self, key: &Key) {
+        let mut listings = self.lock_listings();
+        listings.listings.remove(key);
+        *listings.generations.entry(key.clone()).or_default() += 1;
+    }
+}
+
+impl Default for ListingCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+/// Provider caching the listings of the wrapped provider
+///
+/// Listings changed by operations performed through this provider
+/// (writes, deletes etc.) are dropped from the cache. Changes made
+/// by anything else are only picked up once the listing expires, or
+/// is dropped with `invalidate`.
+pub struct CachedProvider<P: Provider> {
+    provider: P,
+    cache: ListingCache,
+}
+
+impl<P: Provider> CachedProvider<P> {
+    pub fn new(provider: P, cache: ListingCache) -> CachedProvider<P> {
+        CachedProvider { provider, cache }
+    }
+
+    fn key(&self, path: &VirtualPath) -> Key {
+        (
+            self.provider.provider_name().to_owned(),
+            self.provider.resource_name().to_owned(),
+            path.clone(),
+        )
+    }
+
+    /// Drops the listings of given path and of its parent directory
+    fn invalidate_entry(&self, path: &VirtualPath) {
+        self.cache.remove(&self.key(path));
+        if let Some(parent) = path.parent() {
+            self.cache.remove(&self.key(&parent));
+        }
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Provider for CachedProvider<P> {
+    fn provider_name(&self) -> &str {
+        self.provider.provider_name()
+    }
+
+    fn resource_name(&self) -> &str {
+        self.provider.resource_name()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.provider.capabilities()
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        self.provider.initial_path()
+    }
+
+    async fn list(&self, path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        let key = self.key(path);
+        if let Some(objects) = self.cache.get(&key) {
+            debug!(path = %path, "Listing served from cache");
+            return Ok(objects);
+        }
+        let generation = self.cache.generation(&key);
+        let objects = self.provider.list(path).await?;
+        self.cache.insert(key, objects.clone(), generation);
+        Ok(objects)
+    }
+
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError> {
+        self.provider.stat(path).await
+    }
+
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        self.provider.read(path).await
+    }
+
+    async fn read_range(
+        &self,
+        path: &VirtualPath,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        self.provider.read_range(path, offset, length).await
+    }
+
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        let result = self.provider.write(path, stream).await;
+        self.invalidate_entry(path);
+        result
+    }
+
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        let result = self.provider.delete(path).await;
+        self.invalidate_entry(path);
+        result
+    }
+
+    async fn rename(&self, from: &VirtualPath, to: &VirtualPath) -> Result<(), ProviderError> {
+        let result = self.provider.rename(from, to).await;
+        self.invalidate_entry(from);
+        self.invalidate_entry(to);
+        result
+    }
+
+    async fn create_symlink(
+        &self,
+        path: &VirtualPath,
+        target: &OsStr,
+    ) -> Result<(), ProviderError> {
+        let result = self.provider.create_symlink(path, target).await;
+        self.invalidate_entry(path);
+        result
+    }
+
+    async fn create_dir(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        let result = self.provider.create_dir(path).await;
+        self.invalidate_entry(path);
+        result
+    }
+
+    async fn canonicalize(&self, path: &VirtualPath) -> Result<VirtualPath, ProviderError> {
+        self.provider.canonicalize(path).await
+    }
+
+    fn invalidate(&self, path: &VirtualPath) {
+        self.cache.remove(&self.key(path));
+    }
+}
@@ -0,0 +1,149 @@
+//! Module that defines provider errors
+use std::{
+    error::Error,
+    fmt::{self},
+    io,
+    sync::Arc,
+};
+
+/// Enum representing the categories of errors providers can return
+///
+/// * `NotFound` - The requested entry doesn't exist
+/// * `PermissionDenied` - Insufficient permissions to perform the operation
+/// * `Conflict` - The operation conflicts with the current state of the
+///   entry (eg. the entry already exists)
+/// * `Transient` - A temporary failure (eg. a network error or throttling),
+///   repeating the operation may succeed
+/// * `Auth` - Credentials are missing, invalid or expired
+/// * `Unsupported` - The provider doesn't support the operation
+/// * `InvalidInput` - The arguments of the operation are invalid
+/// * `InvalidData` - The data received from the provider is invalid
+/// * `Other` - Any other error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    Conflict,
+    Transient,
+    Auth,
+    Unsupported,
+    InvalidInput,
+    InvalidData,
+    Other,
+}
+
+/// Error struct returned by all providers
+#[derive(Debug, Clone)]
+pub struct ProviderError {
+    kind: ErrorKind,
+    code: String,
+    message: String,
+    source: Option<Arc<dyn Error + Send + Sync>>,
+}
+
+impl ProviderError {
+    /// Creates a new error of given kind, its code is set to the name
+    /// of the kind
+    pub fn new(kind: ErrorKind, message: String) -> ProviderError {
+        ProviderError {
+            kind,
+            code: format!("{:?}", kind),
+            message,
+            source: None,
+        }
+    }
+
+    /// Creates an error signifying that the given operation is not
+    /// supported by the provider
+    pub fn unsupported(operation: &str) -> ProviderError {
+        ProviderError::new(
+            ErrorKind::Unsupported,
+            format!(
+                "Operation '{}' is not supported by this provider",
+                operation
+            ),
+        )
+    }
+
+    /// Sets the provider-specific code of the error (eg. `NoSuchKey`)
+    pub fn with_code(mut self, code: String) -> ProviderError {
+        self.code = code;
+        self
+    }
+
+    /// Sets the underlying cause of the error
+    pub fn with_source(mut self, source: impl Error + Send + Sync + 'static) -> ProviderError {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns `true` if repeating the failed operation may succeed
+    pub fn is_retryable(&self) -> bool {
+        self.kind == ErrorKind::Transient
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ProviderError: {{ Kind: {:?}, Message: {}, Code: {:?} }}",
+            &self.kind, &self.message, &self.code
+        )
+    }
+}
+
+impl Error for ProviderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|s| s.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+impl From<io::Error> for ProviderError {
+    fn from(err: io::Error) -> Self {
+        let (kind, message) = match err.kind() {
+            io::ErrorKind::NotFound => (ErrorKind::NotFound, "File couldn't be found"),
+            io::ErrorKind::PermissionDenied => (
+                ErrorKind::PermissionDenied,
+                "Insufficient file permissions to perform this operation on file",
+            ),
+            io::ErrorKind::AlreadyExists => (ErrorKind::Conflict, "File already exists"),
+            io::ErrorKind::InvalidData => (ErrorKind::InvalidData, "File contains invalid data"),
+            io::ErrorKind::InvalidInput => (ErrorKind::InvalidInput, "Invalid file path"),
+            io::ErrorKind::WriteZero | io::ErrorKind::UnexpectedEof => {
+                (ErrorKind::InvalidData, "Operation was not able to complete")
+            }
+            io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => (
+                ErrorKind::Transient,
+                "Operation was interrupted, it may succeed when repeated",
+            ),
+            io::ErrorKind::Unsupported => {
+                (ErrorKind::Unsupported, "This operation is not supported")
+            }
+            _ => (ErrorKind::Other, "Unexpected error ocurred"),
+        };
+        let code = format!("{:?}", err.kind());
+        ProviderError::new(kind, message.to_owned())
+            .with_code(code)
+            .with_source(err)
+    }
+}
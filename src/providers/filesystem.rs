@@ -1,160 +1,517 @@
-//! Module containing functions used to operate on the local filesystem
+//! Module containing the provider used to operate on the local filesystem
+//!
+//! All the I/O is performed with `tokio::fs`, so slow disks or network
+//! mounts don't block the runtime threads other transfers and the UI use.
+//!
+//! Files are written atomically: the data goes into a temporary file
+//! next to the destination, which is synced to disk and then renamed over
+//! it, so an interrupted write never leaves a truncated file behind. The
+//! directory is synced after the rename as well, so the new file survives
+//! a crash.
 use std::{
     self,
     borrow::Borrow,
-    fs::{self, File},
-    io::{self, BufRead, BufReader, BufWriter, Write},
-    path::{Path, PathBuf},
+    env,
+    ffi::{OsStr, OsString},
+    fs, io,
+    path::{Component, Path, PathBuf},
     pin::Pin,
-    task::Poll,
+    task::{Context, Poll},
 };
 
+use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use futures::{Stream, StreamExt};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter, SeekFrom, Take},
+    runtime::Handle,
+};
+use tokio_util::io::ReaderStream;
+use tracing::{instrument, warn};
 
-use super::{BoxedByteStream, Kind};
-
-/// Struct representing an entry in the local filesystem
-///
-/// It contains the name of the file, the directory it is located
-/// in and its kind (file, directory or undefined)
-#[derive(Clone)]
-pub struct FilesystemObject {
-    pub name: String,
-    pub dir: Option<PathBuf>,
-    pub kind: Kind,
-}
+use super::{
+    err::{ErrorKind, ProviderError},
+    BoxedByteStream, Capabilities, Kind, Metadata, Object, Provider,
+};
+use crate::path::VirtualPath;
+
+/// Default size (in bytes) of the chunks files are read and written in
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 
 pub struct FileBytesStream {
-    reader: BufReader<File>,
+    reader: ReaderStream<Take<File>>,
     size: usize,
 }
 
 impl FileBytesStream {
-    pub fn new(file: File) -> FileBytesStream {
-        let file_len = file
-            .metadata()
-            .expect("Couldn't obtain file metadata, possible permissions issue")
-            .len() as usize;
-        FileBytesStream {
-            reader: BufReader::new(file),
-            size: file_len,
-        }
+    /// Creates a stream yielding the contents of the given file
+    /// in chunks of at most `chunk_size` bytes
+    pub async fn new(file: File, chunk_size: usize) -> Result<FileBytesStream, io::Error> {
+        FileBytesStream::with_range(file, 0, None, chunk_size).await
+    }
+
+    /// Creates a stream yielding at most `length` bytes of the given
+    /// file, starting at `offset`, in chunks of at most `chunk_size`
+    /// bytes. If `length` is `None` the stream ends at the end of the file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::{Stream, StreamExt};
+    /// use versfm::providers::filesystem::FileBytesStream;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let path = std::env::temp_dir().join("versfm-range-example.txt");
+    /// tokio::fs::write(&path, "hello world").await.unwrap();
+    /// let file = tokio::fs::File::open(&path).await.unwrap();
+    ///
+    /// let mut stream = FileBytesStream::with_range(file, 6, Some(3), 2).await.unwrap();
+    /// assert_eq!(stream.size_hint(), (3, Some(3)));
+    /// let mut read = Vec::new();
+    /// while let Some(chunk) = stream.next().await {
+    ///     read.extend_from_slice(&chunk.unwrap());
+    /// }
+    /// assert_eq!(read, b"wor");
+    /// # tokio::fs::remove_file(&path).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn with_range(
+        mut file: File,
+        offset: u64,
+        length: Option<u64>,
+        chunk_size: usize,
+    ) -> Result<FileBytesStream, io::Error> {
+        let file_len = file.metadata().await?.len();
+        let size = file_len
+            .saturating_sub(offset)
+            .min(length.unwrap_or(u64::MAX));
+        file.seek(SeekFrom::Start(offset)).await?;
+        Ok(FileBytesStream {
+            reader: ReaderStream::with_capacity(file.take(size), chunk_size),
+            size: size as usize,
+        })
     }
 }
 
 impl Stream for FileBytesStream {
     type Item = Result<Bytes, io::Error>;
 
-    fn poll_next(
-        mut self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
-        match self.reader.fill_buf() {
-            Ok(bytes_read) => {
-                let consumed = bytes_read.len();
-                if consumed > 0 {
-                    let bytes_read = Bytes::copy_from_slice(bytes_read);
-                    self.reader.consume(consumed);
-                    Poll::Ready(Some(Ok(bytes_read)))
-                } else {
-                    Poll::Ready(None)
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.reader).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.size, Some(self.size))
+    }
+}
+
+/// Temporary file a file is written into before it is renamed to its
+/// final name, it is removed when dropped unless it has been persisted
+struct TempFile {
+    path: Option<PathBuf>,
+}
+
+impl TempFile {
+    /// Returns the path of a new temporary file in the directory of
+    /// `path`, named after the file (eg. `.notes.txt.1a2b3c4d.versfm-tmp`)
+    fn path_for(path: &Path) -> PathBuf {
+        let mut name = OsString::from(".");
+        name.push(path.file_name().unwrap_or_default());
+        name.push(format!(".{:08x}.versfm-tmp", fastrand::u32(..)));
+        path.with_file_name(name)
+    }
+
+    /// Renames the temporary file to `path` and syncs the directory it is
+    /// in, so the rename survives a crash, after which the file is no
+    /// longer removed when dropped
+    ///
+    /// The file is in place once it has been renamed, so failing to sync
+    /// the directory is only logged.
+    async fn persist(mut self, path: &Path) -> Result<(), io::Error> {
+        if let Some(temp_path) = &self.path {
+            tokio::fs::rename(temp_path, path).await?;
+            self.path = None;
+            #[cfg(unix)]
+            if let Some(dir) = path.parent() {
+                if let Err(e) = sync_dir(dir).await {
+                    warn!(path = %dir.display(), error = %e, "Couldn't sync directory");
                 }
             }
-            Err(err) => Poll::Ready(Some(Err(err))),
         }
+        Ok(())
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.size, Some(self.size))
+    /// Removes the temporary file
+    async fn remove(mut self) {
+        if let Some(path) = self.path.take() {
+            let result = tokio::fs::remove_file(&path).await;
+            log_removal(&path, result);
+        }
+    }
+}
+
+impl Drop for TempFile {
+    /// Removes the file of a write that has been abandoned (eg. cancelled)
+    /// on a blocking thread, so a slow filesystem doesn't stall the runtime
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let remove = move || log_removal(&path, fs::remove_file(&path));
+            match Handle::try_current() {
+                Ok(handle) => drop(handle.spawn_blocking(remove)),
+                Err(_) => remove(),
+            }
+        }
     }
 }
 
-/// Gets the list of files present under the provided `path`
-pub fn get_files_list(path: &Path) -> Result<Vec<FilesystemObject>, io::Error> {
-    if fs::metadata(path)?.is_dir() {
-        return Ok(fs::read_dir(path)?
-            .filter(|f| f.is_ok())
-            .map(|f| {
-                let path = f.unwrap().path();
-                let mut file_name = path
-                    .file_name()
-                    .expect("Couldn't obtain file_name from given path")
-                    .to_str()
-                    .expect("Cannot convert non-utf8 filename to string")
-                    .to_owned();
-                let kind: Kind;
-                if let Ok(metadata) = fs::metadata(&path) {
-                    if metadata.is_dir() {
-                        file_name.push_str("/");
-                        kind = Kind::Directory
-                    } else {
-                        kind = Kind::File;
-                    }
-                } else {
-                    kind = Kind::Unknown;
+/// Syncs the entries of the given directory to disk
+#[cfg(unix)]
+async fn sync_dir(dir: &Path) -> Result<(), io::Error> {
+    File::open(dir).await?.sync_all().await
+}
+
+/// Logs the failure to remove a temporary file, if it still exists
+fn log_removal(path: &Path, result: Result<(), io::Error>) {
+    match result {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => warn!(path = %path.display(), error = %e, "Couldn't remove temporary file"),
+    }
+}
+
+/// Provider for the local filesystem integration.
+pub struct FilesystemProvider {
+    user: String,
+    chunk_size: usize,
+}
+
+impl FilesystemProvider {
+    pub fn new() -> FilesystemProvider {
+        FilesystemProvider::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a provider reading and writing files in chunks
+    /// of `chunk_size` bytes
+    pub fn with_chunk_size(chunk_size: usize) -> FilesystemProvider {
+        FilesystemProvider {
+            user: whoami::username(),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Maps given virtual path onto a path in the local filesystem
+    ///
+    /// Every segment has to be a single regular name, so a segment like
+    /// `..` or `/etc` (eg. a name sent by a plugin) can't make the path
+    /// point outside of the directory it was joined onto.
+    fn to_local_path(path: &VirtualPath) -> Result<PathBuf, ProviderError> {
+        let mut local_path = PathBuf::from("/");
+        for segment in path.segments() {
+            let mut components = Path::new(segment).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(name)), None) if name == segment.as_os_str() => {
+                    local_path.push(name)
                 }
-                FilesystemObject {
-                    name: file_name,
-                    dir: path.parent().and_then(|p| Some(p.to_path_buf())),
-                    kind: kind,
+                _ => {
+                    return Err(ProviderError::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid file name '{}'", segment.to_string_lossy()),
+                    ))
                 }
-            })
-            .collect());
-    } else {
-        return Err(io::Error::new(
-            io::ErrorKind::Unsupported,
-            "Given path points to a non-directory file",
-        ));
+            }
+        }
+        Ok(local_path)
+    }
+
+    /// Maps given path in the local filesystem onto a virtual path
+    fn to_virtual_path(path: &Path) -> VirtualPath {
+        VirtualPath::from_segments(
+            "file",
+            "",
+            path.components()
+                .filter_map(|c| match c {
+                    Component::Normal(segment) => Some(segment.to_owned()),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Creates an `Object` describing the file under given `path`
+    async fn make_object(path: &Path) -> Object {
+        let name = path.file_name().map(|n| n.to_owned()).unwrap_or_default();
+        match tokio::fs::symlink_metadata(path).await {
+            Ok(link_metadata) if link_metadata.file_type().is_symlink() => {
+                let target = tokio::fs::read_link(path)
+                    .await
+                    .map(|t| t.into_os_string())
+                    .unwrap_or_default();
+                // Metadata of the link's target, unavailable if the link is broken
+                let target_metadata = tokio::fs::metadata(path).await.ok();
+                Object {
+                    name,
+                    kind: Kind::Symlink {
+                        target,
+                        is_dir: target_metadata.as_ref().is_some_and(|m| m.is_dir()),
+                        broken: target_metadata.is_none(),
+                    },
+                    metadata: Self::make_metadata(
+                        target_metadata.as_ref().unwrap_or(&link_metadata),
+                    ),
+                }
+            }
+            Ok(metadata) => Object {
+                name,
+                kind: Self::make_kind(&metadata.file_type()),
+                metadata: Self::make_metadata(&metadata),
+            },
+            Err(_) => Object {
+                name,
+                kind: Kind::Unknown,
+                metadata: Metadata::default(),
+            },
+        }
+    }
+
+    /// Maps given `fs::FileType` of a non-symlink file onto its `Kind`
+    fn make_kind(file_type: &fs::FileType) -> Kind {
+        if file_type.is_dir() {
+            return Kind::Directory;
+        }
+        if file_type.is_file() {
+            return Kind::File;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_fifo() {
+                return Kind::Fifo;
+            }
+            if file_type.is_socket() {
+                return Kind::Socket;
+            }
+            if file_type.is_block_device() {
+                return Kind::BlockDevice;
+            }
+            if file_type.is_char_device() {
+                return Kind::CharDevice;
+            }
+        }
+        Kind::Unknown
+    }
+
+    /// Maps given `fs::Metadata` onto provider-neutral `Metadata`
+    fn make_metadata(metadata: &fs::Metadata) -> Metadata {
+        #[cfg(unix)]
+        let (permissions, owner) = {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+            (
+                Some(metadata.permissions().mode()),
+                Some(metadata.uid().to_string()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (permissions, owner) = (None, None);
+        Metadata {
+            size: Some(metadata.len()).filter(|_| metadata.is_file()),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            permissions,
+            owner,
+            ..Default::default()
+        }
     }
 }
 
-/// Returns the file stream of a file with given path
-///
-/// # Arguments
-///
-/// * `path` - Path of the file of which the file stream should be obtained
-pub fn get_file_byte_stream(path: &Path) -> Result<FileBytesStream, io::Error> {
-    let file = fs::OpenOptions::new()
-        .read(true)
-        .write(false)
-        .open(path)
-        .unwrap();
-    Ok(FileBytesStream::new(file))
+impl Default for FilesystemProvider {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Writes a file to the local filesystem
-///
-/// # Arguments
-///
-/// * `path` - Path of the new file
-/// * `stream` - Stream from which the file data will be read
-pub async fn write_file_from_stream(
-    path: &Path,
-    stream: Pin<BoxedByteStream>,
-) -> Result<(), io::Error> {
-    let mut writer = BufWriter::new(File::create(path)?);
-    let mut stream = Box::pin(stream);
-    while let Some(chunk) = stream.next().await {
-        if let Err(err) = writer.write(chunk.expect("Couldn't obtain chunk for writing").borrow()) {
-            match err.kind() {
-                io::ErrorKind::Interrupted => continue,
-                _ => return Err(err),
+#[async_trait]
+impl Provider for FilesystemProvider {
+    fn provider_name(&self) -> &str {
+        "local"
+    }
+
+    fn resource_name(&self) -> &str {
+        &self.user
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            directories: true,
+            create_dir: true,
+            rename: true,
+            symlinks: cfg!(unix),
+            range_reads: true,
+            ..Default::default()
+        }
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        env::current_dir()
+            .map(|p| Self::to_virtual_path(&p))
+            .unwrap_or_else(|_| VirtualPath::new("file", ""))
+    }
+
+    /// Gets the list of files present under the provided `path`
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn list(&self, path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        let path = Self::to_local_path(path)?;
+        if tokio::fs::metadata(&path).await?.is_dir() {
+            let mut entries = tokio::fs::read_dir(&path).await?;
+            let mut objects = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                objects.push(Self::make_object(&entry.path()).await);
             }
+            Ok(objects)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Given path points to a non-directory file",
+            )
+            .into())
+        }
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError> {
+        let path = Self::to_local_path(path)?;
+        tokio::fs::symlink_metadata(&path).await?;
+        Ok(Self::make_object(&path).await)
+    }
+
+    /// Returns the file stream of a file with given path
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        let file = File::open(Self::to_local_path(path)?).await?;
+        Ok(Box::pin(FileBytesStream::new(file, self.chunk_size).await?))
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path, offset, length = ?length), err)]
+    async fn read_range(
+        &self,
+        path: &VirtualPath,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        let file = File::open(Self::to_local_path(path)?).await?;
+        Ok(Box::pin(
+            FileBytesStream::with_range(file, offset, length, self.chunk_size).await?,
+        ))
+    }
+
+    /// Writes a file to the local filesystem
+    ///
+    /// The data is written into a temporary file in the same directory,
+    /// which replaces the file under `path` only once all of the data has
+    /// been written and synced to disk. The temporary file is removed if
+    /// the write fails, or is abandoned (eg. the transfer is cancelled).
+    /// An overwritten file's permissions are kept, and if it is a link,
+    /// the file it points to is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the new file
+    /// * `stream` - Stream from which the file data will be read
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        let path = Self::to_local_path(path)?;
+        let path = tokio::fs::canonicalize(&path).await.unwrap_or(path);
+        let temp_path = TempFile::path_for(&path);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await?;
+        let temp = TempFile {
+            path: Some(temp_path.clone()),
         };
+        if let Ok(metadata) = tokio::fs::metadata(&path).await {
+            if let Err(e) = tokio::fs::set_permissions(&temp_path, metadata.permissions()).await {
+                temp.remove().await;
+                return Err(e.into());
+            }
+        }
+
+        let written: Result<(), ProviderError> = async {
+            let mut writer = BufWriter::with_capacity(self.chunk_size, file);
+            let mut stream = Box::pin(stream);
+            while let Some(chunk) = stream.next().await {
+                writer.write_all(chunk?.borrow()).await?;
+            }
+            writer.flush().await?;
+            writer.into_inner().sync_all().await?;
+            Ok(())
+        }
+        .await;
+        match written {
+            Ok(()) => Ok(temp.persist(&path).await?),
+            Err(e) => {
+                temp.remove().await;
+                Err(e)
+            }
+        }
     }
-    Ok(())
-}
 
-/// Removes a file of the given path from the local filesystem
-///
-/// * `path` - Path to the file that should be deleted
-pub fn remove_file(path: &Path) -> Result<(), io::Error> {
-    if fs::metadata(path)?.is_dir() {
-        return Err(io::Error::new(
-            io::ErrorKind::Unsupported,
-            "Deletion of directories is unsupported!",
-        ));
-    }
-    fs::remove_file(path)
+    /// Removes a file of the given path from the local filesystem
+    ///
+    /// * `path` - Path to the file that should be deleted
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        let path = Self::to_local_path(path)?;
+        if tokio::fs::symlink_metadata(&path).await?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Deletion of directories is unsupported!",
+            )
+            .into());
+        }
+        Ok(tokio::fs::remove_file(path).await?)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(from = %from, to = %to), err)]
+    async fn rename(&self, from: &VirtualPath, to: &VirtualPath) -> Result<(), ProviderError> {
+        Ok(tokio::fs::rename(Self::to_local_path(from)?, Self::to_local_path(to)?).await?)
+    }
+
+    #[cfg(unix)]
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn create_symlink(
+        &self,
+        path: &VirtualPath,
+        target: &OsStr,
+    ) -> Result<(), ProviderError> {
+        Ok(tokio::fs::symlink(target, Self::to_local_path(path)?).await?)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn create_dir(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        Ok(tokio::fs::create_dir(Self::to_local_path(path)?).await?)
+    }
+
+    /// Resolves symbolic links and relative components of the path, a
+    /// file that doesn't exist is resolved within its parent directory
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn canonicalize(&self, path: &VirtualPath) -> Result<VirtualPath, ProviderError> {
+        let local_path = Self::to_local_path(path)?;
+        let canonical = match tokio::fs::canonicalize(&local_path).await {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                match (local_path.parent(), local_path.file_name()) {
+                    (Some(parent), Some(name)) => tokio::fs::canonicalize(parent).await?.join(name),
+                    _ => return Err(e.into()),
+                }
+            }
+            canonical => canonical?,
+        };
+        Ok(Self::to_virtual_path(&canonical))
+    }
 }
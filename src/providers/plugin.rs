@@ -0,0 +1,626 @@
+//! Module containing the provider backed by an external executable (plugin)
+//!
+//! # Protocol
+//!
+//! The plugin is spawned once, when the provider is created, and talks
+//! to versfm over its standard input and output using JSON-RPC 2.0.
+//! Every request and every response is a single JSON object followed by
+//! a newline. Requests are sent one at a time, each of them has to be
+//! answered before the next one is sent. Responses with an `id` other
+//! than the one of the pending request are discarded, while error
+//! responses without an `id` (eg. to requests that couldn't be parsed)
+//! answer the pending request. Requests that aren't answered within 60
+//! seconds fail, and their late responses are discarded. Anything the
+//! plugin writes to its standard error is discarded as well.
+//!
+//! Paths are sent as arrays of segments relative to the root of the
+//! resource the plugin exposes (eg. `["releases", "v1.0.tar.gz"]`).
+//! Segments are JSON strings, so they have to be valid UTF-8: operations
+//! on paths with segments that aren't (eg. copying a local file with a
+//! Latin-1 encoded name to a plugin) fail with `ErrorKind::InvalidInput`
+//! instead of sending a lossily converted name, which could refer to
+//! a different file. File contents are encoded with standard base64.
+//!
+//! The plugin has to implement the following methods:
+//!
+//! * `initialize` - `{}` → `{"provider_name": "artifacts",
+//!   "resource_name": "releases", "initial_path": ["v1"]}`, sent right
+//!   after the plugin is spawned. `initial_path` is optional and
+//!   defaults to the root. The optional `"directories": false` member
+//!   signifies that the plugin exposes a flat list of files, that can't
+//!   be navigated
+//! * `list` - `{"path": [...]}` → `[entry, ...]`
+//! * `stat` - `{"path": [...]}` → `entry`
+//! * `read` - `{"path": [...], "offset": 0, "length": 262144}` →
+//!   `{"data": "<base64>"}`, at most `length` bytes starting at
+//!   `offset`. Empty `data` marks the end of the file
+//! * `write` - `{"path": [...], "data": "<base64>", "append": false}` →
+//!   `null`. The first chunk of a file is sent with `append` set to
+//!   `false` and should create or truncate the file, the following
+//!   chunks are sent with `append` set to `true`
+//! * `delete` - `{"path": [...]}` → `null`
+//!
+//! An `entry` is an object of the following form, where only `name` and
+//! `kind` are required and `kind` is one of `file`, `directory`,
+//! `symlink` or `other`. Entries whose `name` is empty, `.`, `..` or
+//! contains `/` or NUL are rejected with `ErrorKind::InvalidData`:
+//!
+//! ```json
+//! {"name": "v1.0.tar.gz", "kind": "file", "size": 1024,
+//!  "modified": "2022-04-01T12:00:00Z", "etag": "abc",
+//!  "sha256": "<base64>", "crc32c": "<base64>",
+//!  "content_type": "application/gzip", "target": null}
+//! ```
+//!
+//! The optional `sha256` and `crc32c` members hold the base64-encoded
+//! checksums of the file (the CRC32C in big-endian byte order), transfers
+//! of the file are verified against them.
+//!
+//! Failures are reported with regular JSON-RPC error objects. The
+//! optional `data.kind` member (eg. `{"kind": "NotFound"}`) names the
+//! `ErrorKind` of the error and `data.code` its provider-specific code.
+//! Errors with the `-32601` (method not found) code are reported as
+//! unsupported operations.
+use std::{
+    ffi::OsString,
+    io,
+    pin::Pin,
+    process::Stdio,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+};
+use tracing::{debug, instrument};
+
+use super::{
+    err::{ErrorKind, ProviderError},
+    BoxedByteStream, Capabilities, Kind, Metadata, Object, Provider,
+};
+use crate::path::VirtualPath;
+
+/// Maximum size (in bytes) of the chunks files are read from plugins in
+const READ_CHUNK_SIZE: usize = 256 * 1024;
+
+/// JSON-RPC code of the error returned for unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Time after which a request the plugin hasn't answered fails
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize)]
+struct Request<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<RpcErrorData>,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorData {
+    kind: Option<String>,
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InitializeResult {
+    provider_name: String,
+    resource_name: String,
+    #[serde(default)]
+    initial_path: Vec<String>,
+    directories: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    name: String,
+    kind: String,
+    target: Option<String>,
+    size: Option<u64>,
+    modified: Option<String>,
+    etag: Option<String>,
+    sha256: Option<String>,
+    crc32c: Option<String>,
+    content_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ReadResult {
+    data: String,
+}
+
+/// Maps the name of an error kind sent by a plugin onto an `ErrorKind`
+fn error_kind(name: &str) -> ErrorKind {
+    match name {
+        "NotFound" => ErrorKind::NotFound,
+        "PermissionDenied" => ErrorKind::PermissionDenied,
+        "Conflict" => ErrorKind::Conflict,
+        "Transient" => ErrorKind::Transient,
+        "Auth" => ErrorKind::Auth,
+        "Unsupported" => ErrorKind::Unsupported,
+        "InvalidInput" => ErrorKind::InvalidInput,
+        "InvalidData" => ErrorKind::InvalidData,
+        _ => ErrorKind::Other,
+    }
+}
+
+impl From<RpcError> for ProviderError {
+    fn from(err: RpcError) -> Self {
+        let data = err.data.unwrap_or(RpcErrorData {
+            kind: None,
+            code: None,
+        });
+        let kind = match (err.code, data.kind) {
+            (METHOD_NOT_FOUND, _) => ErrorKind::Unsupported,
+            (_, Some(kind)) => error_kind(&kind),
+            (_, None) => ErrorKind::Other,
+        };
+        let mut provider_err = ProviderError::new(kind, err.message);
+        if let Some(code) = data.code {
+            provider_err = provider_err.with_code(code);
+        }
+        provider_err
+    }
+}
+
+impl TryFrom<Entry> for Object {
+    type Error = ProviderError;
+
+    fn try_from(entry: Entry) -> Result<Self, Self::Error> {
+        if matches!(entry.name.as_str(), "" | "." | "..") || entry.name.contains(['/', '\0']) {
+            return Err(ProviderError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Plugin sent an invalid file name '{}'",
+                    entry.name.escape_default()
+                ),
+            ));
+        }
+        let kind = match entry.kind.as_str() {
+            "file" => Kind::File,
+            "directory" => Kind::Directory,
+            "symlink" => Kind::Symlink {
+                target: OsString::from(entry.target.unwrap_or_default()),
+                is_dir: false,
+                broken: false,
+            },
+            _ => Kind::Unknown,
+        };
+        Ok(Object {
+            name: OsString::from(entry.name),
+            kind,
+            metadata: Metadata {
+                size: entry.size,
+                modified: entry
+                    .modified
+                    .and_then(|m| DateTime::parse_from_rfc3339(&m).ok())
+                    .map(|m| m.with_timezone(&Utc)),
+                etag: entry.etag,
+                sha256: entry.sha256,
+                crc32c: entry.crc32c,
+                content_type: entry.content_type,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// Creates an error signifying that the plugin sent malformed data
+fn invalid_data(err: impl std::error::Error + Send + Sync + 'static) -> ProviderError {
+    ProviderError::new(
+        ErrorKind::InvalidData,
+        String::from("Plugin sent a malformed message"),
+    )
+    .with_source(err)
+}
+
+/// Creates an error signifying that the plugin can't be communicated with
+fn disconnected(err: io::Error) -> ProviderError {
+    ProviderError::new(
+        ErrorKind::Other,
+        String::from("Couldn't communicate with the plugin"),
+    )
+    .with_source(err)
+}
+
+/// Standard streams of a running plugin, owned by the task talking to it
+struct Pipes {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+impl Pipes {
+    /// Sends a request to the plugin and waits for its response, for at
+    /// most `REPLY_TIMEOUT`
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value, ProviderError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut request = serde_json::to_string(&Request {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        })
+        .map_err(invalid_data)?;
+        request.push('\n');
+        self.stdin
+            .write_all(request.as_bytes())
+            .await
+            .map_err(disconnected)?;
+        self.stdin.flush().await.map_err(disconnected)?;
+
+        let response = tokio::time::timeout(REPLY_TIMEOUT, self.response(id))
+            .await
+            .map_err(|_| {
+                ProviderError::new(
+                    ErrorKind::Transient,
+                    format!("Plugin hasn't answered {} in time", method),
+                )
+                .with_code(String::from("Timeout"))
+            })??;
+        match response.error {
+            Some(err) => Err(err.into()),
+            None => Ok(response.result),
+        }
+    }
+
+    /// Reads responses until the one to the request of given id arrives
+    async fn response(&mut self, id: u64) -> Result<Response, ProviderError> {
+        loop {
+            let line = self.stdout.next_line().await.map_err(disconnected)?;
+            let line = line.ok_or_else(|| {
+                disconnected(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Plugin has exited",
+                ))
+            })?;
+            let response: Response = serde_json::from_str(&line).map_err(invalid_data)?;
+            match response.id {
+                Some(response_id) if response_id == id => return Ok(response),
+                None if response.error.is_some() => return Ok(response),
+                // Responses to requests that timed out before being answered
+                _ => debug!(id = ?response.id, "Discarding response to an abandoned request"),
+            }
+        }
+    }
+}
+
+/// Request waiting to be sent to the plugin, along with the sender of
+/// its result
+struct Call {
+    method: String,
+    params: Value,
+    reply: oneshot::Sender<Result<Value, ProviderError>>,
+}
+
+/// Spawns the task sending requests to the plugin one by one
+///
+/// The task owns the plugin's streams, so callers abandoning their
+/// requests can't interrupt a request or response half-way through.
+fn spawn_io(mut pipes: Pipes, mut calls: UnboundedReceiver<Call>) {
+    tokio::spawn(async move {
+        while let Some(call) = calls.recv().await {
+            // Requests abandoned while queued aren't sent at all
+            if call.reply.is_closed() {
+                continue;
+            }
+            let result = pipes.call(&call.method, call.params).await;
+            call.reply.send(result).ok();
+        }
+    });
+}
+
+/// Connection to a running plugin, the plugin is killed once all of the
+/// clones of its connection are dropped
+#[derive(Clone)]
+struct Connection {
+    calls: UnboundedSender<Call>,
+}
+
+impl Connection {
+    /// Queues a request to the plugin and waits for its response
+    async fn call(&self, method: &str, params: Value) -> Result<Value, ProviderError> {
+        let stopped = || {
+            disconnected(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Plugin connection has been closed",
+            ))
+        };
+        let (reply, result) = oneshot::channel();
+        self.calls
+            .send(Call {
+                method: method.to_owned(),
+                params,
+                reply,
+            })
+            .map_err(|_| stopped())?;
+        result.await.map_err(|_| stopped())?
+    }
+}
+
+/// Stream yielding the contents of a file read from a plugin
+struct PluginBytesStream {
+    chunks: Pin<BoxedByteStream>,
+    size: Option<usize>,
+}
+
+impl Stream for PluginBytesStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.chunks.as_mut().poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.size.unwrap_or(0), self.size)
+    }
+}
+
+/// Provider delegating all of its operations to an external executable
+///
+/// # Examples
+///
+/// ```no_run
+/// use versfm::providers::{plugin::PluginProvider, Provider};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let provider = PluginProvider::new("artifacts-plugin", &["--repo=releases"])
+///     .await
+///     .unwrap();
+/// let entries = provider.list(&provider.initial_path()).await.unwrap();
+/// # }
+/// ```
+pub struct PluginProvider {
+    provider_name: String,
+    resource_name: String,
+    initial_path: Vec<String>,
+    directories: bool,
+    connection: Connection,
+}
+
+impl PluginProvider {
+    /// Spawns the plugin and initializes it
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - Path to the plugin executable
+    /// * `args` - Arguments the plugin is spawned with
+    pub async fn new(program: &str, args: &[&str]) -> Result<PluginProvider, ProviderError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                return Err(disconnected(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "Plugin's standard streams couldn't be captured",
+                )))
+            }
+        };
+        let pipes = Pipes {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            next_id: 0,
+        };
+        let (calls, queued) = mpsc::unbounded_channel();
+        spawn_io(pipes, queued);
+        let connection = Connection { calls };
+        debug!(program, ?args, "Spawned plugin");
+        let init: InitializeResult =
+            serde_json::from_value(connection.call("initialize", json!({})).await?)
+                .map_err(invalid_data)?;
+        Ok(PluginProvider {
+            provider_name: init.provider_name,
+            resource_name: init.resource_name,
+            initial_path: init.initial_path,
+            directories: init.directories.unwrap_or(true),
+            connection,
+        })
+    }
+
+    /// Sends a request to the plugin and deserializes its result
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ProviderError> {
+        let result = self.connection.call(method, params).await?;
+        serde_json::from_value(result).map_err(invalid_data)
+    }
+
+    /// Returns a stream reading at most `length` bytes of the file,
+    /// starting at `offset`, one request per chunk
+    async fn read_chunks(
+        &self,
+        path: &VirtualPath,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        let end = length.map_or(u64::MAX, |l| offset.saturating_add(l));
+        let size = self
+            .stat(path)
+            .await?
+            .metadata
+            .size
+            .map(|s| s.min(end).saturating_sub(offset) as usize);
+        let connection = self.connection.clone();
+        let segments = Self::to_segments(path)?;
+        let chunks = stream::unfold(Some(offset), move |offset: Option<u64>| {
+            let connection = connection.clone();
+            let segments = segments.clone();
+            async move {
+                let offset = offset.filter(|o| *o < end)?;
+                let length = (end - offset).min(READ_CHUNK_SIZE as u64);
+                let params = json!({
+                    "path": segments,
+                    "offset": offset,
+                    "length": length,
+                });
+                let data = connection
+                    .call("read", params)
+                    .await
+                    .and_then(|r| serde_json::from_value::<ReadResult>(r).map_err(invalid_data))
+                    .and_then(|r| base64::decode(r.data).map_err(invalid_data));
+                match data {
+                    Ok(data) if data.is_empty() => None,
+                    Ok(mut data) => {
+                        data.truncate(length as usize);
+                        let next_offset = offset + data.len() as u64;
+                        Some((Ok(Bytes::from(data)), Some(next_offset)))
+                    }
+                    Err(e) => Some((Err(io::Error::other(e)), None)),
+                }
+            }
+        });
+        Ok(Box::pin(PluginBytesStream {
+            chunks: Box::pin(chunks),
+            size,
+        }))
+    }
+
+    /// Maps given path onto the list of segments sent to the plugin,
+    /// fails if any of them isn't valid UTF-8
+    fn to_segments(path: &VirtualPath) -> Result<Vec<String>, ProviderError> {
+        path.segments()
+            .iter()
+            .map(|s| {
+                s.to_str().map(str::to_owned).ok_or_else(|| {
+                    ProviderError::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Name '{}' isn't valid UTF-8, so it can't be sent to the plugin",
+                            s.to_string_lossy()
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Provider for PluginProvider {
+    fn provider_name(&self) -> &str {
+        &self.provider_name
+    }
+
+    fn resource_name(&self) -> &str {
+        &self.resource_name
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            directories: self.directories,
+            range_reads: true,
+            ..Default::default()
+        }
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        VirtualPath::from_segments(
+            "plugin",
+            &self.resource_name,
+            self.initial_path.iter().map(OsString::from).collect(),
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn list(&self, path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        let entries: Vec<Entry> = self
+            .call("list", json!({ "path": Self::to_segments(path)? }))
+            .await?;
+        entries.into_iter().map(Object::try_from).collect()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError> {
+        let entry: Entry = self
+            .call("stat", json!({ "path": Self::to_segments(path)? }))
+            .await?;
+        entry.try_into()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        self.read_chunks(path, 0, None).await
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path, offset, length = ?length), err)]
+    async fn read_range(
+        &self,
+        path: &VirtualPath,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        self.read_chunks(path, offset, length).await
+    }
+
+    /// Sends the file to the plugin, one request per chunk of the stream
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        mut stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        let segments = Self::to_segments(path)?;
+        let mut append = false;
+        while let Some(chunk) = stream.next().await {
+            let params = json!({
+                "path": segments,
+                "data": base64::encode(chunk?),
+                "append": append,
+            });
+            self.call::<Value>("write", params).await?;
+            append = true;
+        }
+        if !append {
+            let params = json!({ "path": segments, "data": "", "append": false });
+            self.call::<Value>("write", params).await?;
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        self.call::<Value>("delete", json!({ "path": Self::to_segments(path)? }))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Module containing the policy operations failed with transient errors
+//! are retried according to
+use std::{future::Future, time::Duration};
+
+use tracing::warn;
+
+use super::err::ProviderError;
+
+/// Default number of times a failed operation is retried
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default delay before the first retry
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Default limit of the delay between retries
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(20);
+
+/// Policy determining how many times, and after what delays, operations
+/// failed with transient errors (see `ProviderError::is_retryable`) are
+/// retried
+///
+/// The delay doubles with every retry, up to `max_delay`, and is then
+/// randomly reduced by up to a half, so retries of operations that
+/// failed at the same time (eg. when throttled) are spread out.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use versfm::providers::retry::RetryPolicy;
+/// let policy = RetryPolicy {
+///     max_retries: 5,
+///     base_delay: Duration::from_secs(1),
+///     max_delay: Duration::from_secs(6),
+/// };
+/// // 1s doubled twice, reduced by up to a half
+/// let delay = policy.delay(3);
+/// assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+/// // Capped at 6s, reduced by up to a half
+/// let delay = policy.delay(5);
+/// assert!(delay >= Duration::from_secs(3) && delay <= Duration::from_secs(6));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries, 0 disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Limit of the delay between retries
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy under which operations are never retried
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the delay before the given retry (counted from 1)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+    }
+
+    /// Returns `true` if an operation that failed with given error after
+    /// being retried `retries` times should be retried once more
+    pub fn should_retry(&self, err: &ProviderError, retries: u32) -> bool {
+        err.is_retryable() && retries < self.max_retries
+    }
+
+    /// Performs the operation created by `operation`, retrying it while
+    /// it fails with transient errors
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the operation, used when logging retries
+    /// * `operation` - Function creating the operation anew for every attempt
+    pub async fn run<T, F, Fut>(&self, name: &str, operation: F) -> Result<T, ProviderError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        self.run_notifying(name, operation, |_, _| ()).await
+    }
+
+    /// Same as `run`, but calls `on_retry` with the number of the retry
+    /// (counted from 1) and the error that caused it, before every retry
+    pub async fn run_notifying<T, F, Fut, R>(
+        &self,
+        name: &str,
+        mut operation: F,
+        mut on_retry: R,
+    ) -> Result<T, ProviderError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+        R: FnMut(u32, ProviderError),
+    {
+        let mut retries = 0;
+        loop {
+            match operation().await {
+                Err(e) if self.should_retry(&e, retries) => {
+                    retries += 1;
+                    let delay = self.delay(retries);
+                    warn!(operation = name, retry = retries, delay = ?delay, error = %e, "Retrying");
+                    on_retry(retries, e);
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+}
@@ -1,209 +1,740 @@
 //! Module containing structs and functions used to communicate with AWS S3
+//!
+//! # Key encoding
+//!
+//! Keys of S3 objects are always valid UTF-8, while names of local files
+//! don't have to be. When a path is mapped onto a key, every byte of its
+//! segments that is not a part of a valid UTF-8 sequence is percent-encoded
+//! (eg. the Latin-1 encoded name `caf\xE9.txt` is uploaded as `caf%E9.txt`).
+//! Valid UTF-8 sequences, including literal '%' characters, are kept as-is,
+//! so the encoding is not reversed when such an object is downloaded.
+//!
+//! # Uploads
+//!
+//! Streams whose exact length is known are uploaded with a single
+//! `PutObject` request. Streams of unknown length are buffered in parts
+//! of `part_size` bytes: if the stream ends before the first part fills
+//! up it is uploaded with `PutObject` as well, otherwise it is uploaded
+//! part by part using a multipart upload, which is aborted if any of
+//! the parts fails to upload, or if the write is abandoned before the
+//! upload has been completed (eg. when its transfer is paused or
+//! cancelled). Streams of known length too large for a
+//! single request are uploaded in parts of at least `part_size` bytes,
+//! enlarged so the whole stream fits in the maximum number of parts.
+//!
+//! # Checksums
+//!
+//! ETags of objects stored unencrypted or with SSE-S3 are the MD5 digests
+//! of their contents (or, for multipart uploads, of the digests of their
+//! parts), so they are used to verify transferred objects. ETags of
+//! objects encrypted with SSE-KMS or SSE-C aren't, so they are left out
+//! of the metadata returned by `stat`, and such objects are verified by
+//! size only.
+//!
+//! S3's additional checksums (`x-amz-checksum-sha256`, `-crc32c` and the
+//! others) are not supported: the S3 client can neither request them on
+//! `PutObject`/`UploadPart` nor read them back from `HeadObject`, and it
+//! doesn't implement `GetObjectAttributes`. Objects are therefore uploaded
+//! without them, and `stat` never reports the `sha256` or `crc32c` of an
+//! object, so S3 objects are only ever verified against their ETags.
 extern crate quick_xml;
 extern crate serde;
 
-use std::error::Error;
+use std::{
+    error::Error,
+    ffi::OsStr,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
 use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
 use rusoto_core::{credential::ProfileProvider, ByteStream, HttpClient, Region, RusotoError};
 use rusoto_s3::{
-    DeleteObjectRequest, GetObjectOutput, GetObjectRequest, ListObjectsV2Request, PutObjectRequest,
-    S3Client, S3,
+    AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload,
+    CompletedPart, CreateMultipartUploadRequest, DeleteObjectRequest, GetObjectError,
+    GetObjectOutput, GetObjectRequest, HeadObjectRequest, ListObjectsV2Request, PutObjectRequest,
+    S3Client, UploadPartRequest, S3,
 };
 use serde::Deserialize;
+use tracing::{debug, instrument, warn};
 
-use super::Kind;
+use super::{
+    err::{ErrorKind, ProviderError},
+    BoxedByteStream, Capabilities, Kind, Metadata, Object, Provider,
+};
+use crate::path::VirtualPath;
 
-/// Struct representing a deserialized XML error returned
-/// by S3
-#[derive(Debug, Deserialize)]
-pub struct S3Error {
-    #[serde(rename = "Code", default)]
-    code: String,
-    #[serde(rename = "Message", default)]
-    message: String,
+/// Minimum size (in bytes) of a part of a multipart upload accepted by S3
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Default size (in bytes) of the parts streams of unknown length are
+/// uploaded in
+pub const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum number of parts of a multipart upload
+const MAX_PARTS: u64 = 10_000;
+
+/// Maximum size (in bytes) of an object uploaded with a single request
+const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Stream yielding the body of an object, sized according to
+/// the `Content-Length` S3 returned for it
+struct ObjectBytesStream {
+    body: ByteStream,
+    size: Option<usize>,
 }
 
-impl S3Error {
-    pub fn code(&self) -> &str {
-        &self.code
+impl Stream for ObjectBytesStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.body).poll_next(cx)
     }
 
-    pub fn message(&self) -> &str {
-        &self.message
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.size.unwrap_or(0), self.size)
     }
 }
 
-/// Struct representing an S3 object
-#[derive(Clone)]
-pub struct S3Object {
-    pub name: String,
-    pub prefix: String,
-    pub kind: Kind,
-    pub size: Option<i64>,
-    pub last_mod: Option<DateTime<Utc>>,
-    pub storage_class: Option<String>,
-    pub owner: Option<String>,
+/// Multipart upload in progress, aborted in the background if it is
+/// dropped before it has finished (eg. when the write uploading it is
+/// abandoned), so its parts aren't left stored in the bucket
+struct MultipartUpload {
+    s3_client: S3Client,
+    request: AbortMultipartUploadRequest,
+    finished: bool,
+}
+
+impl MultipartUpload {
+    /// Aborts the upload, discarding the parts uploaded so far
+    async fn abort(mut self) {
+        self.finished = true;
+        Self::send_abort(&self.s3_client, self.request.clone()).await;
+    }
+
+    async fn send_abort(s3_client: &S3Client, request: AbortMultipartUploadRequest) {
+        let upload_id = request.upload_id.clone();
+        match s3_client.abort_multipart_upload(request).await {
+            Ok(_) => debug!(%upload_id, "Aborted multipart upload"),
+            Err(e) => warn!(%upload_id, error = %e, "Couldn't abort multipart upload"),
+        }
+    }
+}
+
+impl Drop for MultipartUpload {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let (s3_client, request) = (self.s3_client.clone(), self.request.clone());
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move { Self::send_abort(&s3_client, request).await });
+            }
+            Err(_) => warn!(
+                upload_id = %request.upload_id,
+                "Couldn't abort abandoned multipart upload, no runtime is running"
+            ),
+        }
+    }
+}
+
+/// Struct representing a deserialized XML error returned
+/// by S3
+#[derive(Debug, Deserialize)]
+struct S3Error {
+    #[serde(rename = "Code", default)]
+    code: String,
+    #[serde(rename = "Message", default)]
+    message: String,
 }
 
 /// Provider for the S3 bucket integration.
 pub struct S3Provider {
     pub bucket_name: String,
     s3_client: S3Client,
+    part_size: usize,
 }
 
 impl S3Provider {
-    /// Maps provided RusotoError to an S3Error
-    fn handle_error(err: RusotoError<impl Error>) -> S3Error {
+    /// Maps provided RusotoError to a ProviderError
+    fn handle_error<E: Error + Send + Sync + 'static>(err: RusotoError<E>) -> ProviderError {
         match err {
-            RusotoError::Unknown(buf) => {
-                let text = buf.body_as_str();
-                quick_xml::de::from_str(text).expect("Couldn't parse XML into S3Error struct")
+            RusotoError::Service(err) => {
+                ProviderError::new(ErrorKind::Other, err.to_string()).with_source(err)
             }
-            RusotoError::HttpDispatch(err) => S3Error {
-                code: String::from("Request Error"),
-                message: err.to_string(),
-            },
-            RusotoError::Credentials(err) => S3Error {
-                code: String::from("Credentials Error"),
-                message: err.to_string(),
-            },
-            RusotoError::Validation(msg) => S3Error {
-                code: String::from("Validation Error"),
-                message: msg,
-            },
-            RusotoError::ParseError(msg) => S3Error {
-                code: String::from("ParsingError"),
-                message: msg,
-            },
-            _ => S3Error {
-                code: String::from("Unknown Error"),
-                message: String::from("Unknown error ocurred"),
-            },
+            RusotoError::Unknown(response) => {
+                let status = response.status.as_u16();
+                debug!(
+                    status,
+                    body = response.body_as_str(),
+                    "S3 responded with an error"
+                );
+                // Bodies of some responses (eg. the ones to HEAD requests) are empty
+                // or contain something other than an XML error
+                let (code, message) =
+                    match quick_xml::de::from_str::<S3Error>(response.body_as_str()) {
+                        Ok(err) if !err.code.is_empty() => (err.code, err.message),
+                        _ => (
+                            response
+                                .status
+                                .canonical_reason()
+                                .unwrap_or("Unknown Error")
+                                .to_owned(),
+                            format!("S3 responded with status code {}", status),
+                        ),
+                    };
+                ProviderError::new(Self::error_kind(&code, status), message).with_code(code)
+            }
+            RusotoError::HttpDispatch(err) => {
+                ProviderError::new(ErrorKind::Transient, err.to_string())
+                    .with_code(String::from("Request Error"))
+                    .with_source(err)
+            }
+            RusotoError::Credentials(err) => ProviderError::new(ErrorKind::Auth, err.to_string())
+                .with_code(String::from("Credentials Error"))
+                .with_source(err),
+            RusotoError::Validation(msg) => ProviderError::new(ErrorKind::InvalidInput, msg)
+                .with_code(String::from("Validation Error")),
+            RusotoError::ParseError(msg) => ProviderError::new(ErrorKind::InvalidData, msg)
+                .with_code(String::from("ParsingError")),
+            _ => ProviderError::new(ErrorKind::Other, String::from("Unknown error ocurred"))
+                .with_code(String::from("Unknown Error")),
+        }
+    }
+
+    /// Determines the kind of an error based on the S3 error code
+    /// and the HTTP status code of the response
+    fn error_kind(code: &str, status: u16) -> ErrorKind {
+        match (code, status) {
+            ("NoSuchKey" | "NoSuchBucket", _) | (_, 404) => ErrorKind::NotFound,
+            (
+                "InvalidAccessKeyId"
+                | "SignatureDoesNotMatch"
+                | "ExpiredToken"
+                | "InvalidToken"
+                | "TokenRefreshRequired",
+                _,
+            ) => ErrorKind::Auth,
+            ("AccessDenied", _) | (_, 403) => ErrorKind::PermissionDenied,
+            ("SlowDown" | "InternalError" | "ServiceUnavailable" | "RequestTimeout", _)
+            | (_, 500 | 502 | 503 | 504) => ErrorKind::Transient,
+            (_, 409 | 412) => ErrorKind::Conflict,
+            ("InvalidRange", _) | (_, 416) => ErrorKind::InvalidInput,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Maps given path onto an S3 object key
+    ///
+    /// Segments of the path are joined with the '/' character, so a
+    /// key like `/a//b` is represented by the `["", "a", "", "b"]` segments
+    fn path_to_key(path: &VirtualPath) -> String {
+        path.segments()
+            .iter()
+            .map(|s| Self::encode_segment(s))
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
+    /// Maps given path segment onto a valid UTF-8 string, percent-encoding
+    /// bytes which are not a part of a valid UTF-8 sequence
+    fn encode_segment(segment: &OsStr) -> String {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let mut out = String::new();
+            for chunk in segment.as_bytes().utf8_chunks() {
+                out.push_str(chunk.valid());
+                for byte in chunk.invalid() {
+                    out.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            out
+        }
+        #[cfg(not(unix))]
+        segment.to_string_lossy().into_owned()
+    }
+
+    /// Maps given path onto the prefix shared by the keys of all
+    /// the objects located in the directory under given path
+    fn path_to_prefix(path: &VirtualPath) -> String {
+        if path.is_root() {
+            String::new()
+        } else {
+            let mut prefix = Self::path_to_key(path);
+            prefix.push('/');
+            prefix
         }
     }
 
-    pub async fn new(bucket_name: &str, region: Region) -> S3Provider {
+    pub async fn new(bucket_name: &str, region: Region) -> Result<S3Provider, ProviderError> {
+        let http_client = HttpClient::new().map_err(|e| {
+            ProviderError::new(
+                ErrorKind::Other,
+                String::from("Couldn't create HTTP client"),
+            )
+            .with_source(e)
+        })?;
+        let credentials = ProfileProvider::new().map_err(|e| {
+            ProviderError::new(
+                ErrorKind::Auth,
+                String::from("Please provide your aws credentials in the .aws file"),
+            )
+            .with_source(e)
+        })?;
+        let s3_client = S3Client::new_with(http_client, credentials, region);
+        Ok(S3Provider::with_client(bucket_name, s3_client))
+    }
+
+    /// Creates a provider of the given bucket, sending its requests
+    /// through the given client
+    pub fn with_client(bucket_name: &str, s3_client: S3Client) -> S3Provider {
         S3Provider {
             bucket_name: bucket_name.to_owned(),
-            s3_client: S3Client::new_with(
-                HttpClient::new().expect("Couldn't create HTTP client"),
-                ProfileProvider::new()
-                    .expect("Please provide your aws credentials in the .aws file"),
-                region,
-            ),
+            s3_client,
+            part_size: DEFAULT_PART_SIZE,
         }
     }
 
-    /// Lists objects present in the S3 bucket, that contain a given prefix
+    /// Sets the size (in bytes) of the parts streams of unknown length
+    /// are uploaded in, sizes smaller than `MIN_PART_SIZE` are rounded up
+    pub fn with_part_size(mut self, part_size: usize) -> S3Provider {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    /// Lists top-level objects and directories present in the S3 bucket
+    /// under a given prefix
     ///
     /// # Arguments
     ///
-    /// * `prefix` - The prefix of the objects that should be obtained
-    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<S3Object>, S3Error> {
-        let mut request = ListObjectsV2Request::default();
-        request.bucket = self.bucket_name.clone();
-        let mut prefix = prefix.to_owned();
-        request.prefix = if prefix.is_empty() {
-            None
-        } else {
-            prefix.push_str("/");
-            Some(prefix.clone())
-        };
-        let objects = self.s3_client.list_objects_v2(request);
-        let response = match objects.await.map_err(Self::handle_error)?.contents {
-            None => return Ok(Vec::new()),
-            Some(contents) => contents,
-        };
-        let result = response
-            .into_iter()
-            .filter(|i| {
-                let key = i.key.to_owned().expect("Couldn't obtain S3 object key");
-                let (prefix, file_name) = key.split_at(prefix.len());
-                // Ensures function returns only top-level files and directories
-                // for given prefix. (entries like foo/bar.txt are omitted)
-                match (prefix, file_name) {
-                    ("", name) => match name.find("/") {
-                        None => true,
-                        Some(i) => i == name.len() - 1,
-                    },
-                    (_, "") => false,
-                    (_, name) => {
-                        let last_char = name.chars().last().expect("Name is empty");
-                        let separator_count = name.matches('/').count();
-                        separator_count == 0 || (separator_count == 1 && last_char == '/')
-                    }
+    /// * `prefix` - The prefix of the objects that should be obtained,
+    ///   it should either be empty or end with the '/' character
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<Object>, ProviderError> {
+        let mut result = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let request = ListObjectsV2Request {
+                bucket: self.bucket_name.clone(),
+                prefix: Some(prefix.to_owned()).filter(|p| !p.is_empty()),
+                delimiter: Some(String::from("/")),
+                continuation_token,
+                ..Default::default()
+            };
+            let response = self
+                .s3_client
+                .list_objects_v2(request)
+                .await
+                .map_err(Self::handle_error)?;
+            // Common prefixes represent the directories present under the prefix
+            result.extend(
+                response
+                    .common_prefixes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|p| p.prefix)
+                    .filter_map(|p| {
+                        let name = p.strip_prefix(prefix)?.strip_suffix('/')?;
+                        Some(Object {
+                            name: name.into(),
+                            kind: Kind::Directory,
+                            metadata: Metadata::default(),
+                        })
+                    }),
+            );
+            result.extend(
+                response
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|i| {
+                        let name = i.key.as_ref()?.strip_prefix(prefix)?.to_owned();
+                        // Omits the marker object of the listed directory itself
+                        if name.is_empty() {
+                            return None;
+                        }
+                        Some(Object {
+                            name: name.into(),
+                            kind: Kind::File,
+                            metadata: Metadata {
+                                size: i.size.and_then(|s| u64::try_from(s).ok()),
+                                modified: i
+                                    .last_modified
+                                    .and_then(|d| DateTime::parse_from_rfc3339(&d).ok())
+                                    .map(|d| d.with_timezone(&Utc)),
+                                owner: i.owner.and_then(|o| o.display_name),
+                                etag: i.e_tag,
+                                storage_class: i.storage_class,
+                                ..Default::default()
+                            },
+                        })
+                    }),
+            );
+            match response.next_continuation_token {
+                Some(token) if response.is_truncated == Some(true) => {
+                    continuation_token = Some(token)
                 }
-            })
-            .map(|i| {
-                let key = i.key.to_owned().unwrap();
-                let (prefix, file_name) = key.split_at(prefix.len());
-                let kind: Kind;
-                if file_name.chars().last().unwrap() == '/' {
-                    kind = Kind::Directory;
-                } else {
-                    kind = Kind::File;
-                }
-                S3Object {
-                    name: file_name.to_owned(),
-                    prefix: prefix.to_owned(),
-                    kind,
-                    size: i.size,
-                    last_mod: Some(
-                        DateTime::parse_from_rfc3339(i.last_modified.unwrap().as_str())
-                            .expect("Couldn't parse object's last modification date from string")
-                            .with_timezone(&Utc),
-                    ),
-                    storage_class: i.storage_class,
-                    owner: match i.owner {
-                        Some(own) => own.display_name,
-                        None => None,
-                    },
+                _ => break,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Gets the object of given name, or the given byte range of it
+    ///
+    /// * `object_name` - Key of the object
+    /// * `range` - OPTIONAL value of the HTTP `Range` header (eg. `bytes=0-99`)
+    async fn get_object(
+        &self,
+        object_name: &str,
+        range: Option<String>,
+    ) -> Result<GetObjectOutput, ProviderError> {
+        let request = GetObjectRequest {
+            bucket: self.bucket_name.to_owned(),
+            key: object_name.to_owned(),
+            range,
+            ..Default::default()
+        };
+
+        self.s3_client
+            .get_object(request)
+            .await
+            .map_err(|e| match e {
+                RusotoError::Service(GetObjectError::NoSuchKey(msg)) => {
+                    ProviderError::new(ErrorKind::NotFound, msg)
+                        .with_code(String::from("NoSuchKey"))
                 }
+                e => Self::handle_error(e),
             })
-            .collect();
-        Ok(result)
     }
 
-    /// Gets the file stream of an S3 object of provided name
-    pub async fn download_object(&self, object_name: &str) -> Result<ByteStream, S3Error> {
-        let object: GetObjectOutput = self.get_object(object_name).await?;
-        Ok(object.body.expect("Couldn't get object body"))
+    /// Uploads an object of given key with a single request
+    async fn put_object(&self, key: String, body: ByteStream) -> Result<(), ProviderError> {
+        let request = PutObjectRequest {
+            bucket: self.bucket_name.clone(),
+            key,
+            body: Some(body),
+            ..Default::default()
+        };
+        self.s3_client
+            .put_object(request)
+            .await
+            .map_err(Self::handle_error)?;
+        Ok(())
     }
 
-    async fn get_object(&self, object_name: &str) -> Result<GetObjectOutput, S3Error> {
-        let mut request = GetObjectRequest::default();
-        request.bucket = self.bucket_name.to_owned();
-        request.key = object_name.to_owned();
+    /// Reads chunks of the stream into `buffer` until it holds at least
+    /// `part_size` bytes, or the stream ends
+    async fn fill_part(
+        stream: &mut Pin<BoxedByteStream>,
+        buffer: &mut BytesMut,
+        part_size: usize,
+    ) -> Result<(), ProviderError> {
+        while buffer.len() < part_size {
+            match stream.next().await {
+                Some(chunk) => buffer.extend_from_slice(&chunk?),
+                None => break,
+            }
+        }
+        Ok(())
+    }
 
-        Ok(self
+    /// Uploads an object of given key with a multipart upload, the
+    /// upload is aborted if any of its parts fails to upload
+    ///
+    /// * `key` - Key of the object
+    /// * `stream` - Stream the parts are read from
+    /// * `buffer` - Data already read from the stream
+    /// * `part_size` - Size (in bytes) of the parts
+    async fn put_multipart(
+        &self,
+        key: String,
+        stream: Pin<BoxedByteStream>,
+        buffer: BytesMut,
+        part_size: usize,
+    ) -> Result<(), ProviderError> {
+        let request = CreateMultipartUploadRequest {
+            bucket: self.bucket_name.clone(),
+            key: key.clone(),
+            ..Default::default()
+        };
+        let upload_id = self
             .s3_client
-            .get_object(request)
+            .create_multipart_upload(request)
+            .await
+            .map_err(Self::handle_error)?
+            .upload_id
+            .ok_or_else(|| {
+                ProviderError::new(
+                    ErrorKind::InvalidData,
+                    String::from("S3 didn't return the id of the multipart upload"),
+                )
+            })?;
+
+        let mut upload = MultipartUpload {
+            s3_client: self.s3_client.clone(),
+            request: AbortMultipartUploadRequest {
+                bucket: self.bucket_name.clone(),
+                key: key.clone(),
+                upload_id: upload_id.clone(),
+                ..Default::default()
+            },
+            finished: false,
+        };
+        let result = match self
+            .upload_parts(&key, &upload_id, stream, buffer, part_size)
             .await
-            .map_err(Self::handle_error)?)
+        {
+            Ok(parts) => {
+                let request = CompleteMultipartUploadRequest {
+                    bucket: self.bucket_name.clone(),
+                    key: key.clone(),
+                    upload_id: upload_id.clone(),
+                    multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+                    ..Default::default()
+                };
+                self.s3_client
+                    .complete_multipart_upload(request)
+                    .await
+                    .map(|_| ())
+                    .map_err(Self::handle_error)
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => upload.finished = true,
+            // The error of the upload is more relevant than the error of the abort
+            Err(_) => upload.abort().await,
+        }
+        result
     }
 
-    /// Deletes an S3 object of the provided name from the S3 bucket
-    pub async fn delete_object(&self, object_name: &str) -> Result<(), S3Error> {
-        let mut request = DeleteObjectRequest::default();
-        request.bucket = self.bucket_name.clone();
-        request.key = object_name.to_owned();
-        self.s3_client
-            .delete_object(request)
+    /// Uploads the stream part by part, returns the list of uploaded parts
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        mut stream: Pin<BoxedByteStream>,
+        mut buffer: BytesMut,
+        part_size: usize,
+    ) -> Result<Vec<CompletedPart>, ProviderError> {
+        let mut parts = Vec::new();
+        loop {
+            Self::fill_part(&mut stream, &mut buffer, part_size).await?;
+            if buffer.is_empty() {
+                return Ok(parts);
+            }
+            let part_number = parts.len() as i64 + 1;
+            if part_number as u64 > MAX_PARTS {
+                return Err(ProviderError::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "File doesn't fit in {} parts of {} bytes",
+                        MAX_PARTS, part_size
+                    ),
+                ));
+            }
+            let part = buffer.split_to(part_size.min(buffer.len())).freeze();
+            let part_len = part.len();
+            let request = UploadPartRequest {
+                bucket: self.bucket_name.clone(),
+                key: key.to_owned(),
+                upload_id: upload_id.to_owned(),
+                part_number,
+                content_length: Some(part_len as i64),
+                body: Some(ByteStream::new_with_size(
+                    futures::stream::once(async move { Ok(part) }),
+                    part_len,
+                )),
+                ..Default::default()
+            };
+            let output = self
+                .s3_client
+                .upload_part(request)
+                .await
+                .map_err(Self::handle_error)?;
+            debug!(part_number, size = part_len, "Uploaded part");
+            parts.push(CompletedPart {
+                e_tag: output.e_tag,
+                part_number: Some(part_number),
+            });
+        }
+    }
+
+    /// Extracts the body stream out of the given object
+    fn into_body(object: GetObjectOutput) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        match object.body {
+            Some(body) => Ok(Box::pin(ObjectBytesStream {
+                body,
+                size: object.content_length.map(|l| l as usize),
+            })),
+            None => Err(ProviderError::new(
+                ErrorKind::InvalidData,
+                String::from("S3 returned an object without a body"),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for S3Provider {
+    fn provider_name(&self) -> &str {
+        "S3"
+    }
+
+    fn resource_name(&self) -> &str {
+        &self.bucket_name
+    }
+
+    /// Directories are emulated with key prefixes delimited with '/'
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            directories: true,
+            range_reads: true,
+            multipart_part_size: Some(self.part_size as u64),
+            multipart_max_parts: Some(MAX_PARTS),
+            ..Default::default()
+        }
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        VirtualPath::new("s3", &self.bucket_name)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn list(&self, path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        self.list_objects(&Self::path_to_prefix(path)).await
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError> {
+        let name = path.file_name().unwrap_or_default().to_owned();
+        if path.is_root() {
+            return Ok(Object {
+                name,
+                kind: Kind::Directory,
+                metadata: Metadata::default(),
+            });
+        }
+        let request = HeadObjectRequest {
+            bucket: self.bucket_name.clone(),
+            key: Self::path_to_key(path),
+            ..Default::default()
+        };
+        let head = self
+            .s3_client
+            .head_object(request)
             .await
             .map_err(Self::handle_error)?;
-        Ok(())
+        let encrypted_with_key = head.sse_customer_algorithm.is_some()
+            || head.server_side_encryption.as_deref() == Some("aws:kms");
+        Ok(Object {
+            name,
+            kind: Kind::File,
+            metadata: Metadata {
+                size: head.content_length.and_then(|s| u64::try_from(s).ok()),
+                modified: head
+                    .last_modified
+                    .and_then(|d| DateTime::parse_from_rfc2822(&d).ok())
+                    .map(|d| d.with_timezone(&Utc)),
+                etag: head.e_tag.filter(|_| !encrypted_with_key),
+                storage_class: head.storage_class,
+                content_type: head.content_type,
+                encrypted_with_key: Some(encrypted_with_key),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Gets the file stream of an S3 object of provided name
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        let object = self.get_object(&Self::path_to_key(path), None).await?;
+        Self::into_body(object)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(path = %path, offset, length = ?length), err)]
+    async fn read_range(
+        &self,
+        path: &VirtualPath,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        let range = match length {
+            Some(0) => return Ok(Box::pin(futures::stream::empty())),
+            Some(length) => match offset.checked_add(length - 1) {
+                Some(last) => format!("bytes={}-{}", offset, last),
+                None => {
+                    return Err(ProviderError::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Range of {} bytes at offset {} is too large",
+                            length, offset
+                        ),
+                    ))
+                }
+            },
+            None => format!("bytes={}-", offset),
+        };
+        let object = self
+            .get_object(&Self::path_to_key(path), Some(range))
+            .await?;
+        Self::into_body(object)
     }
 
     /// Saves a given object from the file stream to a given location
-    pub async fn put_object(&self, object_name: &str, content: ByteStream) -> Result<(), S3Error> {
-        let mut request = PutObjectRequest::default();
-        request.bucket = self.bucket_name.clone();
-        request.key = object_name.to_owned();
-        request.body = Some(content);
+    ///
+    /// See the module documentation for how streams are uploaded
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        mut stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        let key = Self::path_to_key(path);
+        let size = match stream.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper as u64),
+            _ => None,
+        };
+        match size {
+            Some(size) if size <= MAX_PUT_SIZE => {
+                self.put_object(key, ByteStream::new_with_size(stream, size as usize))
+                    .await
+            }
+            _ => {
+                let part_size = self
+                    .capabilities()
+                    .part_size(size)
+                    .map_or(self.part_size, |s| s as usize);
+                let mut buffer = BytesMut::new();
+                Self::fill_part(&mut stream, &mut buffer, part_size).await?;
+                if buffer.len() < part_size {
+                    let size = buffer.len();
+                    let body = futures::stream::once(async move { Ok(buffer.freeze()) });
+                    self.put_object(key, ByteStream::new_with_size(body, size))
+                        .await
+                } else {
+                    self.put_multipart(key, stream, buffer, part_size).await
+                }
+            }
+        }
+    }
 
+    /// Deletes an S3 object of the provided name from the S3 bucket
+    #[instrument(level = "debug", skip_all, fields(path = %path), err)]
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        let request = DeleteObjectRequest {
+            bucket: self.bucket_name.clone(),
+            key: Self::path_to_key(path),
+            ..Default::default()
+        };
         self.s3_client
-            .put_object(request)
+            .delete_object(request)
             .await
             .map_err(Self::handle_error)?;
         Ok(())
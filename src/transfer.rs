@@ -0,0 +1,1674 @@
+//! Module containing the engine transferring files between providers
+//!
+//! Copies, moves and deletions are submitted to a `TransferEngine` as
+//! `Job`s. The engine queues them and performs them in background tasks,
+//! with at most `max_jobs` of them running at the same time (and at most
+//! `max_jobs_per_provider` using the same provider). Jobs using the same
+//! provider are started in the order they were submitted in. The engine
+//! reports how they progress through `TransferEvent`s and keeps the
+//! current state of every job, so it can be inspected at any time with
+//! `jobs`. Unfinished jobs can be paused, resumed and cancelled.
+//! Operations failed with transient errors are retried according to
+//! the `RetryPolicy` of the engine. Files already present in the
+//! destination are treated according to the `ConflictPolicy`, which may
+//! leave the decision to the user. Checksums of transferred files are
+//! computed as they are streamed, and compared with the checksums of
+//! their destinations once they are written.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use versfm::{
+//!     path::VirtualPath,
+//!     providers::{filesystem::FilesystemProvider, Provider},
+//!     transfer::{Job, JobState, Location, TransferEngine, TransferEvent, TransferOptions},
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let dir = std::env::temp_dir();
+//! tokio::fs::write(dir.join("versfm-transfer-example.txt"), "hello").await.unwrap();
+//! let provider: Arc<dyn Provider> = Arc::new(FilesystemProvider::new());
+//! let dir: VirtualPath = format!("file://{}", dir.display()).parse().unwrap();
+//!
+//! let (engine, mut events) = TransferEngine::new(TransferOptions::default());
+//! let id = engine.submit(Job::copy_file(
+//!     Location::new(provider.clone(), dir.join("versfm-transfer-example.txt")),
+//!     Location::new(provider.clone(), dir.join("versfm-transfer-example.copy")),
+//! ));
+//! while let Some(event) = events.recv().await {
+//!     if let TransferEvent::StateChanged { state, .. } = event {
+//!         if state.is_finished() {
+//!             assert!(matches!(state, JobState::Completed));
+//!             break;
+//!         }
+//!     }
+//! }
+//! assert_eq!(engine.job(id).unwrap().transferred, 5);
+//! # provider.delete(&dir.join("versfm-transfer-example.txt")).await.unwrap();
+//! # provider.delete(&dir.join("versfm-transfer-example.copy")).await.unwrap();
+//! # }
+//! ```
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ffi::OsString,
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{future, Future};
+use tokio::{
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot, watch, Notify, OwnedSemaphorePermit, Semaphore,
+    },
+    task::JoinHandle,
+};
+use tracing::{info, info_span, warn, Instrument};
+
+pub use self::checksum::Checksums;
+pub use self::conflict::{Conflict, ConflictPolicy, Resolution};
+use self::{
+    checksum::{read_checksums, read_size, Hasher},
+    conflict::with_suffix,
+    stream::{cancellable, cancelled_error, interrupted, pausable, ProgressStream},
+};
+use crate::{
+    path::VirtualPath,
+    providers::{
+        err::{ErrorKind, ProviderError},
+        retry::RetryPolicy,
+        BoxedByteStream, Kind, LinkPolicy, Metadata, Provider,
+    },
+};
+
+mod checksum;
+mod conflict;
+mod stream;
+
+/// Default maximum number of jobs running at the same time
+pub const DEFAULT_MAX_JOBS: usize = 8;
+/// Default maximum number of running jobs using the same resource
+pub const DEFAULT_MAX_JOBS_PER_PROVIDER: usize = 4;
+
+/// Minimum time between two progress events of the same job
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// File exposed by a provider
+#[derive(Clone)]
+pub struct Location {
+    pub provider: Arc<dyn Provider>,
+    pub path: VirtualPath,
+}
+
+impl Location {
+    pub fn new(provider: Arc<dyn Provider>, path: VirtualPath) -> Location {
+        Location { provider, path }
+    }
+
+    /// Returns `true` if both locations refer to the same file of the same
+    /// resource, comparing their canonical paths (see `Provider::canonicalize`)
+    ///
+    /// Paths that can't be canonicalized are compared as they are.
+    pub async fn is_same_file(&self, other: &Location) -> bool {
+        if provider_key(self) != provider_key(other) {
+            return false;
+        }
+        let (path, other_path) = futures::join!(
+            self.provider.canonicalize(&self.path),
+            other.provider.canonicalize(&other.path)
+        );
+        match (path, other_path) {
+            (Ok(path), Ok(other_path)) => path == other_path,
+            _ => self.path == other.path,
+        }
+    }
+}
+
+/// Enum representing the operations jobs perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Copy,
+    Move,
+    Delete,
+}
+
+/// Operation on a single file, performed by the `TransferEngine`
+#[derive(Clone)]
+pub struct Job {
+    kind: JobKind,
+    source: Location,
+    destination: Option<Location>,
+    size_hint: Option<u64>,
+}
+
+impl Job {
+    /// Creates a job copying the file `from` to `to`
+    pub fn copy_file(from: Location, to: Location) -> Job {
+        Job {
+            kind: JobKind::Copy,
+            source: from,
+            destination: Some(to),
+            size_hint: None,
+        }
+    }
+
+    /// Creates a job moving the file `from` to `to`
+    ///
+    /// The source is deleted only once it has been transferred, and the
+    /// size (and, if enabled, the checksums) of the written file has been
+    /// verified. If it can't be deleted the job fails, leaving the file in
+    /// both places. Jobs copying or moving a file onto itself fail without
+    /// touching it.
+    pub fn move_file(from: Location, to: Location) -> Job {
+        Job {
+            kind: JobKind::Move,
+            source: from,
+            destination: Some(to),
+            size_hint: None,
+        }
+    }
+
+    /// Creates a job deleting the file under given location
+    pub fn delete_file(location: Location) -> Job {
+        Job {
+            kind: JobKind::Delete,
+            source: location,
+            destination: None,
+            size_hint: None,
+        }
+    }
+
+    /// Sets the size of the file known before the job has started (eg.
+    /// from a listing), so the progress of queued jobs can be estimated
+    pub fn with_size_hint(mut self, size: Option<u64>) -> Job {
+        self.size_hint = size;
+        self
+    }
+
+    pub fn kind(&self) -> JobKind {
+        self.kind
+    }
+
+    pub fn source(&self) -> &Location {
+        &self.source
+    }
+
+    pub fn destination(&self) -> Option<&Location> {
+        self.destination.as_ref()
+    }
+}
+
+/// Identifier of a job, unique within the engine it was submitted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Enum representing the states a job can be in
+///
+/// * `Queued` - The job is waiting for the jobs using the same providers
+///   submitted before it, or for the concurrency limits to allow it to start
+///   (or, if it has been paused while running, to continue)
+/// * `Running` - The job is being performed
+/// * `Paused` - The job has been paused, until it is resumed no more
+///   data is transferred (or, if it hasn't started yet, it isn't started)
+/// * `Completed` - The job has been performed successfully
+/// * `Skipped` - There was nothing to transfer (eg. the file is a link
+///   and links are skipped), or the file already present in the
+///   destination has been kept
+/// * `Cancelled` - The job has been cancelled before it was completed
+/// * `Failed` - The job has failed with the given error
+#[derive(Debug, Clone)]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Skipped,
+    Cancelled,
+    Failed(ProviderError),
+}
+
+impl JobState {
+    /// Returns `true` if the job is over, whatever its result
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self,
+            JobState::Queued | JobState::Running | JobState::Paused
+        )
+    }
+}
+
+/// Snapshot of a job and of its progress
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub source: VirtualPath,
+    pub destination: Option<VirtualPath>,
+    pub state: JobState,
+    /// Number of bytes transferred so far
+    pub transferred: u64,
+    /// Size of the transferred file, if known
+    pub size: Option<u64>,
+    /// Time the job has started running at
+    pub started: Option<Instant>,
+    /// Time the job has finished at
+    pub finished: Option<Instant>,
+    /// Time the job has spent paused after it had started, not counting
+    /// the pause it is currently in
+    pub paused: Duration,
+    /// Number of times the operations of the job have been retried
+    pub retries: u32,
+    /// Conflict with the file already present in the destination, that
+    /// the job is waiting for a decision on (see `TransferEngine::resolve`)
+    pub conflict: Option<Conflict>,
+    /// Checksums of the transferred data, if they have been computed
+    pub checksums: Option<Checksums>,
+    paused_at: Option<Instant>,
+}
+
+impl JobInfo {
+    /// Returns the progress of the job, measured from the time it has
+    /// started running at, not counting the time it has spent paused
+    pub fn progress(&self) -> Progress {
+        let elapsed = match self.started {
+            Some(started) => {
+                let end = self
+                    .paused_at
+                    .or(self.finished)
+                    .unwrap_or_else(Instant::now);
+                end.saturating_duration_since(started)
+                    .saturating_sub(self.paused)
+            }
+            None => Duration::ZERO,
+        };
+        Progress {
+            transferred: self.transferred,
+            size: self.size,
+            elapsed,
+        }
+    }
+}
+
+/// Amount of data transferred by a job (or a batch of jobs) over time
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use versfm::transfer::Progress;
+/// let progress = Progress {
+///     transferred: 300,
+///     size: Some(1200),
+///     elapsed: Duration::from_secs(3),
+/// };
+/// assert_eq!(progress.percentage(), Some(25.0));
+/// assert_eq!(progress.throughput(), Some(100.0));
+/// assert_eq!(progress.eta(), Some(Duration::from_secs(9)));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Progress {
+    /// Number of bytes transferred so far
+    pub transferred: u64,
+    /// Total number of bytes to transfer, if known
+    pub size: Option<u64>,
+    /// Time spent transferring
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns the percentage of bytes transferred so far, if the size
+    /// is known
+    pub fn percentage(&self) -> Option<f64> {
+        self.size.map(|size| match size {
+            0 => 100.0,
+            size => (self.transferred as f64 * 100.0 / size as f64).min(100.0),
+        })
+    }
+
+    /// Returns the average number of bytes transferred per second, if
+    /// any time has elapsed
+    pub fn throughput(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        (secs > 0.0).then(|| self.transferred as f64 / secs)
+    }
+
+    /// Returns the estimated time left until the transfer is over, if
+    /// the size is known and the transfer has made any progress
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.size?.saturating_sub(self.transferred);
+        let throughput = self.throughput().filter(|t| *t > 0.0)?;
+        Some(Duration::from_secs_f64(remaining as f64 / throughput))
+    }
+}
+
+/// Enum representing the events the engine reports about its jobs
+///
+/// * `StateChanged` - The job has moved into the given state
+/// * `Progress` - More bytes of the file have been transferred, progress
+///   events of a job are sent at most every 100ms
+/// * `Retrying` - An operation of the job has failed with the given
+///   transient error, and is retried for the `retry`-th time
+/// * `Conflict` - The destination of the job already exists, and the job
+///   waits until it is told what to do with it
+#[derive(Debug, Clone)]
+pub enum TransferEvent {
+    StateChanged {
+        id: JobId,
+        state: JobState,
+    },
+    Progress {
+        id: JobId,
+        transferred: u64,
+    },
+    Retrying {
+        id: JobId,
+        retry: u32,
+        error: ProviderError,
+    },
+    Conflict {
+        id: JobId,
+        conflict: Box<Conflict>,
+    },
+}
+
+/// Number of jobs of a batch in each of the states
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JobSummary {
+    pub queued: usize,
+    pub running: usize,
+    pub paused: usize,
+    /// Jobs that have been completed or skipped
+    pub done: usize,
+    pub cancelled: usize,
+    pub failed: usize,
+    /// Progress of the copies and moves of the batch, the size is only
+    /// known if the sizes of all of the files are known
+    pub progress: Progress,
+}
+
+impl JobSummary {
+    /// Returns the number of jobs that haven't finished yet
+    pub fn unfinished(&self) -> usize {
+        self.queued + self.running + self.paused
+    }
+
+    /// Returns the number of jobs in the batch
+    pub fn total(&self) -> usize {
+        self.unfinished() + self.done + self.cancelled + self.failed
+    }
+}
+
+/// Options determining how the engine performs jobs
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    /// How symbolic links should be transferred
+    pub link_policy: LinkPolicy,
+    /// Maximum number of jobs running at the same time
+    pub max_jobs: usize,
+    /// Maximum number of running jobs using the same resource of a
+    /// provider (eg. the same S3 bucket)
+    pub max_jobs_per_provider: usize,
+    /// How operations failed with transient errors should be retried
+    pub retry: RetryPolicy,
+    /// How files already present in the destination should be treated
+    pub conflict_policy: ConflictPolicy,
+    /// Whether checksums of transferred files should be computed and
+    /// compared with the checksums of their destinations
+    pub verify_checksums: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            link_policy: LinkPolicy::default(),
+            max_jobs: DEFAULT_MAX_JOBS,
+            max_jobs_per_provider: DEFAULT_MAX_JOBS_PER_PROVIDER,
+            retry: RetryPolicy::default(),
+            conflict_policy: ConflictPolicy::default(),
+            verify_checksums: true,
+        }
+    }
+}
+
+/// Enum representing what the task performing a job is told to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// Jobs of the engine, along with the batch they belong to
+#[derive(Default)]
+struct Jobs {
+    infos: BTreeMap<JobId, JobInfo>,
+    /// Controls of the jobs that haven't finished yet
+    controls: HashMap<JobId, watch::Sender<Control>>,
+    /// Jobs that have been paused before they were started, they are
+    /// queued again once they are resumed
+    parked: HashMap<JobId, Job>,
+    /// First job of the current batch, and the time it was submitted at
+    batch: Option<(JobId, Instant)>,
+    /// Senders of the decisions the jobs with conflicts are waiting for
+    decisions: HashMap<JobId, oneshot::Sender<Resolution>>,
+    /// Policy overriding the engine's conflict policy for the rest of
+    /// the current batch
+    conflict_policy: Option<ConflictPolicy>,
+    /// Permits of the concurrency limits held by the running jobs, jobs
+    /// give them back while they are paused
+    permits: HashMap<JobId, Permits>,
+    /// Jobs resumed after they had been paused while running, waiting
+    /// for the dispatcher to give them permits again
+    resumed: VecDeque<Resumed>,
+    /// Tasks performing the jobs that have started, but haven't finished
+    tasks: HashMap<JobId, JoinHandle<()>>,
+}
+
+/// Job resumed after it had been paused while running, along with the
+/// providers it uses and the sender it is told it can continue through
+struct Resumed {
+    id: JobId,
+    keys: Vec<ProviderKey>,
+    continued: oneshot::Sender<()>,
+}
+
+impl Jobs {
+    fn insert(&mut self, info: JobInfo) {
+        if self.controls.is_empty() {
+            self.batch = Some((info.id, Instant::now()));
+            self.conflict_policy = None;
+        }
+        self.controls
+            .insert(info.id, watch::channel(Control::Run).0);
+        self.infos.insert(info.id, info);
+    }
+
+    /// Changes the state of the given job, returns `false` if the job
+    /// has already finished, in which case its state can't be changed
+    fn set_state(&mut self, id: JobId, state: JobState) -> bool {
+        let job = match self.infos.get_mut(&id) {
+            Some(job) if !job.state.is_finished() => job,
+            _ => return false,
+        };
+        let now = Instant::now();
+        if let Some(paused_at) = job.paused_at.take() {
+            job.paused += now.saturating_duration_since(paused_at);
+        }
+        match &state {
+            JobState::Running if job.started.is_none() => job.started = Some(now),
+            JobState::Paused if job.started.is_some() => job.paused_at = Some(now),
+            state if state.is_finished() => {
+                job.finished = Some(now);
+                job.conflict = None;
+                self.controls.remove(&id);
+                self.parked.remove(&id);
+                self.decisions.remove(&id);
+                self.permits.remove(&id);
+                self.tasks.remove(&id);
+            }
+            _ => (),
+        }
+        job.state = state;
+        true
+    }
+
+    /// Tells the task performing given job what to do
+    fn control(&self, id: JobId, control: Control) {
+        if let Some(sender) = self.controls.get(&id) {
+            sender.send_replace(control);
+        }
+    }
+
+    /// Tells the given job, if it waits for a decision on a conflict,
+    /// what to do with the file already present in its destination
+    fn decide(&mut self, id: JobId, resolution: Resolution) {
+        if let Some(sender) = self.decisions.remove(&id) {
+            sender.send(resolution).ok();
+        }
+        if let Some(job) = self.infos.get_mut(&id) {
+            job.conflict = None;
+        }
+    }
+}
+
+/// State shared between the engine and the tasks performing its jobs
+struct Shared {
+    options: TransferOptions,
+    jobs: Mutex<Jobs>,
+    events: UnboundedSender<TransferEvent>,
+    /// Wakes the dispatcher up, once permits are released or a job
+    /// waits for them
+    wake: Arc<Notify>,
+}
+
+impl Shared {
+    fn lock_jobs(&self) -> MutexGuard<'_, Jobs> {
+        self.jobs.lock().expect("Couldn't lock jobs mutex")
+    }
+
+    /// Changes the state of the given job and reports it, unless the
+    /// job has already finished
+    fn set_state(&self, id: JobId, state: JobState) {
+        if self.lock_jobs().set_state(id, state.clone()) {
+            self.events
+                .send(TransferEvent::StateChanged { id, state })
+                .ok();
+        }
+    }
+
+    fn set_size(&self, id: JobId, size: Option<u64>) {
+        if let Some(job) = self.lock_jobs().infos.get_mut(&id) {
+            job.size = size.or(job.size);
+        }
+    }
+
+    fn set_transferred(&self, id: JobId, transferred: u64) {
+        if let Some(job) = self.lock_jobs().infos.get_mut(&id) {
+            job.transferred = transferred;
+        }
+    }
+
+    fn set_destination(&self, id: JobId, destination: VirtualPath) {
+        if let Some(job) = self.lock_jobs().infos.get_mut(&id) {
+            job.destination = Some(destination);
+        }
+    }
+
+    fn set_checksums(&self, id: JobId, checksums: Checksums) {
+        if let Some(job) = self.lock_jobs().infos.get_mut(&id) {
+            job.checksums = Some(checksums);
+        }
+    }
+
+    fn transferred(&self, id: JobId) -> u64 {
+        self.lock_jobs()
+            .infos
+            .get(&id)
+            .map_or(0, |job| job.transferred)
+    }
+
+    /// Records that an operation of the given job is retried after it
+    /// has failed with `error`, and reports it
+    fn add_retry(&self, id: JobId, error: ProviderError) {
+        let retry = match self.lock_jobs().infos.get_mut(&id) {
+            Some(job) => {
+                job.retries += 1;
+                job.retries
+            }
+            None => return,
+        };
+        self.events
+            .send(TransferEvent::Retrying { id, retry, error })
+            .ok();
+    }
+}
+
+/// Performs the operation created by `operation`, retrying it according
+/// to the engine's retry policy while it fails with transient errors
+///
+/// Every attempt, and the delays between them, are abandoned as soon as
+/// the job is cancelled.
+async fn retrying<T, F, Fut>(
+    shared: &Shared,
+    id: JobId,
+    control: &mut watch::Receiver<Control>,
+    name: &str,
+    operation: F,
+) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let run = shared
+        .options
+        .retry
+        .run_notifying(name, operation, |_, e| shared.add_retry(id, e));
+    cancellable(control, run).await
+}
+
+/// Engine performing copies, moves and deletions of files
+///
+/// Clones of the engine share the same jobs.
+#[derive(Clone)]
+pub struct TransferEngine {
+    shared: Arc<Shared>,
+    queue: UnboundedSender<(JobId, Job)>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TransferEngine {
+    /// Creates an engine performing jobs according to given options,
+    /// along with the receiving end of the channel its events are sent to
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(options: TransferOptions) -> (TransferEngine, UnboundedReceiver<TransferEvent>) {
+        let (events, rx) = mpsc::unbounded_channel();
+        let (queue, queued) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            options,
+            jobs: Mutex::new(Jobs::default()),
+            events,
+            wake: Arc::new(Notify::new()),
+        });
+        spawn_dispatcher(shared.clone(), queued);
+        let engine = TransferEngine {
+            shared,
+            queue,
+            next_id: Arc::new(AtomicU64::new(1)),
+        };
+        (engine, rx)
+    }
+
+    pub fn options(&self) -> &TransferOptions {
+        &self.shared.options
+    }
+
+    /// Adds the given job at the end of the queue
+    ///
+    /// Jobs are started as soon as the concurrency limits allow it, the
+    /// ones using the same provider in the order they were submitted in.
+    pub fn submit(&self, job: Job) -> JobId {
+        let id = JobId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.shared.lock_jobs().insert(JobInfo {
+            id,
+            kind: job.kind,
+            source: job.source.path.clone(),
+            destination: job.destination.as_ref().map(|d| d.path.clone()),
+            state: JobState::Queued,
+            transferred: 0,
+            size: job.size_hint,
+            started: None,
+            finished: None,
+            paused: Duration::ZERO,
+            retries: 0,
+            conflict: None,
+            checksums: None,
+            paused_at: None,
+        });
+        self.shared.set_state(id, JobState::Queued);
+        self.enqueue(id, job);
+        id
+    }
+
+    /// Sends given job to the dispatcher
+    fn enqueue(&self, id: JobId, job: Job) {
+        if self.queue.send((id, job)).is_err() {
+            let e = ProviderError::new(
+                ErrorKind::Other,
+                String::from("Transfer engine has stopped"),
+            );
+            self.shared.set_state(id, JobState::Failed(e));
+        }
+    }
+
+    /// Pauses the given job, if it is queued or running
+    ///
+    /// A running job abandons the transfer in progress, letting a queued
+    /// job run in its place, and transfers the file from the start once
+    /// it is resumed, while a queued one isn't started until it is resumed.
+    pub fn pause(&self, id: JobId) {
+        let pausable = {
+            let jobs = self.shared.lock_jobs();
+            let pausable = jobs
+                .infos
+                .get(&id)
+                .is_some_and(|job| matches!(job.state, JobState::Queued | JobState::Running));
+            if pausable {
+                jobs.control(id, Control::Pause);
+            }
+            pausable
+        };
+        if pausable {
+            self.shared.set_state(id, JobState::Paused);
+        }
+    }
+
+    /// Resumes the given job, if it has been paused
+    ///
+    /// A job that has been paused before it was started is queued again,
+    /// behind the jobs that are already queued. A job that has been
+    /// paused while running gives its place in the concurrency limits
+    /// back, so it stays queued until the limits allow it to continue.
+    pub fn resume(&self, id: JobId) {
+        let (state, parked) = {
+            let mut jobs = self.shared.lock_jobs();
+            let state = match jobs.infos.get(&id) {
+                Some(job) if matches!(job.state, JobState::Paused) => {
+                    match job.started.is_some() && jobs.permits.contains_key(&id) {
+                        true => JobState::Running,
+                        false => JobState::Queued,
+                    }
+                }
+                _ => return,
+            };
+            jobs.control(id, Control::Run);
+            (state, jobs.parked.remove(&id))
+        };
+        self.shared.set_state(id, state);
+        // Jobs paused while running may already wait for their permits
+        self.shared.wake.notify_one();
+        if let Some(job) = parked {
+            self.enqueue(id, job);
+        }
+    }
+
+    /// Cancels the given job, if it hasn't finished yet
+    ///
+    /// The transfer of a running job is interrupted, and the file it was
+    /// writing is deleted, unless it existed before the job was started.
+    pub fn cancel(&self, id: JobId) {
+        let started = {
+            let jobs = self.shared.lock_jobs();
+            jobs.control(id, Control::Cancel);
+            match jobs.infos.get(&id) {
+                Some(job) if !job.state.is_finished() => job.started.is_some(),
+                _ => return,
+            }
+        };
+        // Running jobs finish as cancelled once their task notices it
+        if !started {
+            self.shared.set_state(id, JobState::Cancelled);
+        }
+    }
+
+    /// Tells the given job what to do with the file already present in
+    /// its destination, if it waits for a decision on it
+    pub fn resolve(&self, id: JobId, resolution: Resolution) {
+        self.shared.lock_jobs().decide(id, resolution);
+    }
+
+    /// Resolves the conflicts all of the jobs wait for decisions on, as
+    /// well as any conflicts of the rest of the current batch, the same way
+    pub fn resolve_all(&self, resolution: Resolution) {
+        let mut jobs = self.shared.lock_jobs();
+        jobs.conflict_policy = Some(resolution.into());
+        let ids: Vec<JobId> = jobs.decisions.keys().copied().collect();
+        for id in ids {
+            jobs.decide(id, resolution);
+        }
+    }
+
+    /// Returns the snapshots of the jobs waiting for a decision on what
+    /// to do with the files already present in their destinations, in
+    /// the order they were submitted in
+    pub fn conflicts(&self) -> Vec<JobInfo> {
+        self.shared
+            .lock_jobs()
+            .infos
+            .values()
+            .filter(|job| job.conflict.is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the ids of the unfinished jobs
+    fn unfinished_ids(&self) -> Vec<JobId> {
+        self.shared.lock_jobs().controls.keys().copied().collect()
+    }
+
+    /// Pauses all of the queued and running jobs
+    pub fn pause_all(&self) {
+        let mut ids = self.unfinished_ids();
+        ids.sort();
+        for id in ids {
+            self.pause(id);
+        }
+    }
+
+    /// Resumes all of the paused jobs, in the order they were submitted in
+    pub fn resume_all(&self) {
+        let mut ids = self.unfinished_ids();
+        ids.sort();
+        for id in ids {
+            self.resume(id);
+        }
+    }
+
+    /// Cancels all of the jobs that haven't finished yet
+    pub fn cancel_all(&self) {
+        for id in self.unfinished_ids() {
+            self.cancel(id);
+        }
+    }
+
+    /// Cancels all of the jobs that haven't finished yet, and waits until
+    /// the tasks performing them have stopped
+    ///
+    /// Moves whose files have already been transferred finish deleting
+    /// their sources first.
+    pub async fn shutdown(&self) {
+        self.cancel_all();
+        let tasks: Vec<JoinHandle<()>> = self
+            .shared
+            .lock_jobs()
+            .tasks
+            .drain()
+            .map(|(_, task)| task)
+            .collect();
+        future::join_all(tasks).await;
+    }
+
+    /// Returns the snapshot of the job with given id
+    pub fn job(&self, id: JobId) -> Option<JobInfo> {
+        self.shared.lock_jobs().infos.get(&id).cloned()
+    }
+
+    /// Returns the snapshots of all the jobs, in the order they were
+    /// submitted in
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.shared.lock_jobs().infos.values().cloned().collect()
+    }
+
+    /// Returns the snapshots of the jobs that have started, but haven't
+    /// finished yet (ie. are running or have been paused while running)
+    pub fn active_jobs(&self) -> Vec<JobInfo> {
+        self.shared
+            .lock_jobs()
+            .infos
+            .values()
+            .filter(|job| job.started.is_some() && !job.state.is_finished())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the number of jobs of the current batch in each of the
+    /// states, along with the progress of the whole batch
+    ///
+    /// A batch consists of the jobs submitted since the engine was last
+    /// idle, ie. since all of the jobs submitted before have finished.
+    pub fn summary(&self) -> JobSummary {
+        let jobs = self.shared.lock_jobs();
+        let (first, submitted) = match jobs.batch {
+            Some(batch) => batch,
+            None => return JobSummary::default(),
+        };
+        let mut summary = JobSummary {
+            progress: Progress {
+                size: Some(0),
+                elapsed: submitted.elapsed(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for job in jobs.infos.range(first..).map(|(_, job)| job) {
+            match job.state {
+                JobState::Queued => summary.queued += 1,
+                JobState::Running => summary.running += 1,
+                JobState::Paused => summary.paused += 1,
+                JobState::Completed | JobState::Skipped => summary.done += 1,
+                JobState::Cancelled => summary.cancelled += 1,
+                JobState::Failed(_) => summary.failed += 1,
+            }
+            if job.kind == JobKind::Delete
+                || matches!(
+                    job.state,
+                    JobState::Skipped | JobState::Cancelled | JobState::Failed(_)
+                )
+            {
+                continue;
+            }
+            let progress = &mut summary.progress;
+            progress.transferred += job.transferred;
+            progress.size = progress.size.zip(job.size).map(|(a, b)| a + b);
+        }
+        summary
+    }
+}
+
+/// Key identifying the resource a provider operates on
+type ProviderKey = (String, String);
+
+fn provider_key(location: &Location) -> ProviderKey {
+    (
+        location.provider.provider_name().to_owned(),
+        location.provider.resource_name().to_owned(),
+    )
+}
+
+/// Permits of the concurrency limits held by a running job, the
+/// dispatcher is woken up once they are released
+struct Permits {
+    permits: Vec<OwnedSemaphorePermit>,
+    released: Arc<Notify>,
+}
+
+impl Drop for Permits {
+    fn drop(&mut self) {
+        self.permits.clear();
+        self.released.notify_one();
+    }
+}
+
+/// Concurrency limits of the engine, the global one and the ones of
+/// every provider
+struct Limits {
+    global: Arc<Semaphore>,
+    per_provider: HashMap<ProviderKey, Arc<Semaphore>>,
+    max_jobs_per_provider: usize,
+    released: Arc<Notify>,
+}
+
+impl Limits {
+    /// Takes the permits needed to run a job using the given providers,
+    /// returns `None` if any of the limits is reached
+    fn try_acquire(&mut self, keys: &[ProviderKey]) -> Option<Permits> {
+        let mut permits = vec![self.global.clone().try_acquire_owned().ok()?];
+        for key in keys {
+            let max = self.max_jobs_per_provider;
+            let semaphore = self
+                .per_provider
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(max)));
+            permits.push(semaphore.clone().try_acquire_owned().ok()?);
+        }
+        Some(Permits {
+            permits,
+            released: self.released.clone(),
+        })
+    }
+}
+
+/// Returns the keys of the providers the given job uses
+fn provider_keys(job: &Job) -> Vec<ProviderKey> {
+    let mut keys = vec![provider_key(&job.source)];
+    if let Some(to) = &job.destination {
+        keys.push(provider_key(to));
+    }
+    keys.dedup();
+    keys
+}
+
+/// Spawns the task starting queued jobs once the global limit and the
+/// limits of the providers they use allow it
+///
+/// Jobs using the same provider are started in the order they were
+/// queued in, but a job waiting for a busy provider doesn't hold back
+/// the jobs queued after it that use other providers. Jobs cancelled
+/// while queued are dropped, and jobs paused while queued are parked
+/// until they are resumed.
+fn spawn_dispatcher(shared: Arc<Shared>, mut queued: UnboundedReceiver<(JobId, Job)>) {
+    tokio::spawn(async move {
+        let mut limits = Limits {
+            global: Arc::new(Semaphore::new(shared.options.max_jobs.max(1))),
+            per_provider: HashMap::new(),
+            max_jobs_per_provider: shared.options.max_jobs_per_provider.max(1),
+            released: shared.wake.clone(),
+        };
+        let mut pending: VecDeque<(JobId, Job)> = VecDeque::new();
+        let mut open = true;
+        while open || !pending.is_empty() {
+            start_ready(&shared, &mut limits, &mut pending);
+            tokio::select! {
+                job = queued.recv(), if open => match job {
+                    Some(job) => pending.push_back(job),
+                    None => open = false,
+                },
+                _ = shared.wake.notified() => (),
+            }
+        }
+    });
+}
+
+/// Starts the pending jobs whose limits allow it, in order, after giving
+/// permits to the resumed jobs waiting for them
+fn start_ready(shared: &Arc<Shared>, limits: &mut Limits, pending: &mut VecDeque<(JobId, Job)>) {
+    let mut jobs = shared.lock_jobs();
+    // Providers of the jobs left waiting, the jobs after them using the
+    // same providers wait as well, so they don't overtake them
+    let mut blocked: Vec<ProviderKey> = Vec::new();
+    let mut waiting = VecDeque::new();
+    while let Some(resumed) = jobs.resumed.pop_front() {
+        // Jobs paused or cancelled again stop waiting on their own
+        if resumed.continued.is_closed() {
+            continue;
+        }
+        let queued = matches!(
+            jobs.infos.get(&resumed.id).map(|job| &job.state),
+            Some(JobState::Queued)
+        );
+        if !queued {
+            waiting.push_back(resumed);
+            continue;
+        }
+        let permits = match resumed.keys.iter().any(|key| blocked.contains(key)) {
+            true => None,
+            false => limits.try_acquire(&resumed.keys),
+        };
+        match permits {
+            Some(permits) => {
+                start(shared, &mut jobs, resumed.id, permits);
+                resumed.continued.send(()).ok();
+            }
+            None => {
+                blocked.extend(resumed.keys.iter().cloned());
+                waiting.push_back(resumed);
+            }
+        }
+    }
+    jobs.resumed = waiting;
+
+    let mut waiting = VecDeque::new();
+    while let Some((id, job)) = pending.pop_front() {
+        match jobs.infos.get(&id).map(|job| &job.state) {
+            Some(JobState::Queued) => (),
+            Some(JobState::Paused) => {
+                jobs.parked.insert(id, job);
+                continue;
+            }
+            _ => continue,
+        }
+        let keys = provider_keys(&job);
+        let permits = match keys.iter().any(|key| blocked.contains(key)) {
+            true => None,
+            false => limits.try_acquire(&keys),
+        };
+        match permits {
+            Some(permits) => {
+                start(shared, &mut jobs, id, permits);
+                let task = tokio::spawn(run_job(shared.clone(), id, job, keys));
+                jobs.tasks.insert(id, task);
+            }
+            None => {
+                blocked.extend(keys);
+                waiting.push_back((id, job));
+            }
+        }
+    }
+    *pending = waiting;
+}
+
+/// Moves the given job into the running state, handing it the permits
+/// it runs with
+fn start(shared: &Shared, jobs: &mut Jobs, id: JobId, permits: Permits) {
+    jobs.permits.insert(id, permits);
+    if jobs.set_state(id, JobState::Running) {
+        shared
+            .events
+            .send(TransferEvent::StateChanged {
+                id,
+                state: JobState::Running,
+            })
+            .ok();
+    }
+}
+
+/// Place of a running job in the concurrency limits, given back while
+/// the job is paused, so paused jobs don't hold back the queued ones
+struct Slots {
+    shared: Arc<Shared>,
+    id: JobId,
+    keys: Vec<ProviderKey>,
+}
+
+impl Slots {
+    /// Waits while the job is paused, returns `true` if it has been
+    /// paused and then resumed, and fails if it is cancelled
+    ///
+    /// The permits of the job are released once it is paused, and taken
+    /// again (waiting in the queue if needed) once it is resumed.
+    async fn wait_while_paused(
+        &self,
+        control: &mut watch::Receiver<Control>,
+    ) -> Result<bool, ProviderError> {
+        let mut paused = false;
+        loop {
+            let current = *control.borrow_and_update();
+            match current {
+                Control::Cancel => return Err(cancelled_error()),
+                Control::Pause => {
+                    paused = true;
+                    self.release();
+                    if control.changed().await.is_err() {
+                        future::pending::<()>().await;
+                    }
+                }
+                Control::Run if paused => {
+                    if self.reacquire(control).await {
+                        return Ok(true);
+                    }
+                }
+                Control::Run => return Ok(false),
+            }
+        }
+    }
+
+    fn release(&self) {
+        let permits = self.shared.lock_jobs().permits.remove(&self.id);
+        if permits.is_some() {
+            info!("Paused, released the job's place in the queue");
+        }
+    }
+
+    /// Waits until the dispatcher gives the job its permits back, returns
+    /// `false` if the job is paused or cancelled meanwhile
+    async fn reacquire(&self, control: &mut watch::Receiver<Control>) -> bool {
+        let continued = {
+            let mut jobs = self.shared.lock_jobs();
+            if jobs.permits.contains_key(&self.id) {
+                return true;
+            }
+            let (sender, continued) = oneshot::channel();
+            jobs.resumed.push_back(Resumed {
+                id: self.id,
+                keys: self.keys.clone(),
+                continued: sender,
+            });
+            // The job may have been resumed before it released its permits
+            let running = matches!(
+                jobs.infos.get(&self.id).map(|job| &job.state),
+                Some(JobState::Running)
+            );
+            if running && jobs.set_state(self.id, JobState::Queued) {
+                self.shared
+                    .events
+                    .send(TransferEvent::StateChanged {
+                        id: self.id,
+                        state: JobState::Queued,
+                    })
+                    .ok();
+            }
+            continued
+        };
+        self.shared.wake.notify_one();
+        tokio::select! {
+            continued = continued => continued.is_ok(),
+            _ = interrupted(control) => false,
+        }
+    }
+}
+
+/// Runs the given job, which has already been moved into the running
+/// state by the dispatcher, until it finishes
+async fn run_job(shared: Arc<Shared>, id: JobId, job: Job, keys: Vec<ProviderKey>) {
+    let mut control = match shared.lock_jobs().controls.get(&id) {
+        Some(sender) => sender.subscribe(),
+        None => return,
+    };
+    let slots = Slots {
+        shared: shared.clone(),
+        id,
+        keys,
+    };
+
+    let span = match &job.destination {
+        Some(to) => {
+            info_span!("transfer", job = %id, kind = ?job.kind, from = %job.source.path, to = %to.path)
+        }
+        None => info_span!("transfer", job = %id, kind = ?job.kind, path = %job.source.path),
+    };
+    async {
+        // The job may have been paused right after it was started
+        let result = match slots.wait_while_paused(&mut control).await {
+            Ok(_) => perform(&shared, id, &job, &slots, control.clone()).await,
+            Err(e) => Err(e),
+        };
+        let state = match result {
+            Ok(state) => state,
+            Err(_) if *control.borrow() == Control::Cancel => {
+                info!("Cancelled");
+                JobState::Cancelled
+            }
+            Err(e) => JobState::Failed(e),
+        };
+        shared.set_state(id, state);
+    }
+    .instrument(span)
+    .await;
+}
+
+/// Enum representing what should be transferred for a given file
+enum Transfer {
+    Skip,
+    Stream(Pin<BoxedByteStream>),
+    Link(OsString),
+}
+
+/// Determines what should be transferred for the given file according
+/// to the link policy, and opens its stream if needed
+async fn open_transfer(
+    from: &Location,
+    kind: Kind,
+    link_policy: LinkPolicy,
+) -> Result<Transfer, ProviderError> {
+    match (kind, link_policy) {
+        (Kind::Symlink { .. }, LinkPolicy::Skip) => Ok(Transfer::Skip),
+        (Kind::Symlink { target, .. }, LinkPolicy::Recreate) => Ok(Transfer::Link(target)),
+        (Kind::Symlink { is_dir: true, .. }, LinkPolicy::Follow) => Err(ProviderError::new(
+            ErrorKind::Unsupported,
+            String::from("Following links to directories is not supported"),
+        )),
+        (kind, _) if kind.is_special() => Ok(Transfer::Skip),
+        _ => Ok(Transfer::Stream(from.provider.read(&from.path).await?)),
+    }
+}
+
+/// Decides what should be done with the file already present in the
+/// destination, waiting for the user's decision if the policy says so
+async fn resolve_conflict(
+    shared: &Shared,
+    id: JobId,
+    control: &mut watch::Receiver<Control>,
+    conflict: Conflict,
+) -> Result<Resolution, ProviderError> {
+    let decision = {
+        let mut jobs = shared.lock_jobs();
+        let policy = jobs
+            .conflict_policy
+            .unwrap_or(shared.options.conflict_policy);
+        if let Some(resolution) = policy.resolve(&conflict) {
+            return Ok(resolution);
+        }
+        let (sender, decision) = oneshot::channel();
+        jobs.decisions.insert(id, sender);
+        if let Some(job) = jobs.infos.get_mut(&id) {
+            job.conflict = Some(conflict.clone());
+        }
+        decision
+    };
+    info!("Destination already exists, waiting for a decision");
+    shared
+        .events
+        .send(TransferEvent::Conflict {
+            id,
+            conflict: Box::new(conflict),
+        })
+        .ok();
+    cancellable(control, async {
+        decision.await.map_err(|_| {
+            ProviderError::new(
+                ErrorKind::Other,
+                String::from("Transfer engine has stopped"),
+            )
+        })
+    })
+    .await
+}
+
+/// Returns whether the transferred file and the file of the same size
+/// already present in its destination have different checksums, if the
+/// conflict is resolved with `ConflictPolicy::OverwriteIfDifferent` and
+/// checksums are verified
+///
+/// The checksums are computed for the file that doesn't report any, or
+/// for both of them if neither does. Returns `None` if the files should
+/// be compared by size only.
+async fn checksums_differ(
+    shared: &Shared,
+    id: JobId,
+    control: &mut watch::Receiver<Control>,
+    from: &Location,
+    to: &Location,
+    conflict: &Conflict,
+) -> Result<Option<bool>, ProviderError> {
+    let policy = shared
+        .lock_jobs()
+        .conflict_policy
+        .unwrap_or(shared.options.conflict_policy);
+    let (source, destination) = (&conflict.source, &conflict.destination);
+    let same_size = source.size.is_some() && source.size == destination.size;
+    if policy != ConflictPolicy::OverwriteIfDifferent
+        || !shared.options.verify_checksums
+        || !same_size
+    {
+        return Ok(None);
+    }
+    // ETags of objects encrypted with a key aren't checksums of their data
+    let reports_checksums = |metadata: &Metadata| {
+        metadata.encrypted_with_key != Some(true)
+            && (metadata.sha256.is_some() || metadata.crc32c.is_some() || metadata.etag.is_some())
+    };
+    let matches = if reports_checksums(destination) {
+        // Files are uploaded in parts of the size the destination uses
+        let part_size = to.provider.capabilities().part_size(source.size);
+        let checksums = retrying(shared, id, control, "checksum", || {
+            read_checksums(from, part_size)
+        })
+        .await?;
+        checksums.matches(destination)
+    } else if reports_checksums(source) {
+        // The size of the parts of the source's multipart ETag is unknown
+        let checksums =
+            retrying(shared, id, control, "checksum", || read_checksums(to, None)).await?;
+        checksums.matches(source)
+    } else {
+        let source = retrying(shared, id, control, "checksum", || {
+            read_checksums(from, None)
+        })
+        .await?;
+        let destination =
+            retrying(shared, id, control, "checksum", || read_checksums(to, None)).await?;
+        Some(source.sha256 == destination.sha256)
+    };
+    Ok(matches.map(|matches| !matches))
+}
+
+/// Returns the first path, made by appending a numbered suffix to the
+/// name of the destination, that no file exists under
+async fn free_path(
+    shared: &Shared,
+    id: JobId,
+    control: &mut watch::Receiver<Control>,
+    to: &Location,
+) -> Result<VirtualPath, ProviderError> {
+    let mut n = 1;
+    loop {
+        let path = with_suffix(&to.path, n);
+        match retrying(shared, id, control, "stat", || to.provider.stat(&path)).await {
+            Ok(_) => n += 1,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(path),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Enum representing what has been written to the destination of a job
+///
+/// * `File` - File, along with the number of bytes written to it and
+///   their checksums, if they have been computed
+enum Written {
+    Nothing,
+    Link,
+    File {
+        size: u64,
+        checksums: Option<Checksums>,
+    },
+}
+
+/// Checks that the destination holds the whole transferred file, by
+/// comparing its size with the number of transferred bytes, and those
+/// with the size the source had before it was transferred
+///
+/// If the checksums of the transferred data have been computed, they are
+/// also compared with the checksums the providers report for the source
+/// and for the destination. If the destination doesn't report any, it
+/// is read back and its checksums are computed, unless it is encrypted
+/// with a key of its own, in which case it is verified by size only.
+/// Without checksums, a destination that doesn't report its size is
+/// read back to count its bytes, so the source of a move is never
+/// deleted before its transfer has been checked.
+async fn verify(
+    shared: &Shared,
+    id: JobId,
+    control: &mut watch::Receiver<Control>,
+    slots: &Slots,
+    to: &Location,
+    written: &Written,
+    source: &Metadata,
+) -> Result<(), ProviderError> {
+    let object = retrying(shared, id, control, "stat", || to.provider.stat(&to.path)).await?;
+    let (written, checksums) = match written {
+        Written::File { size, checksums } => (*size, checksums.as_ref()),
+        _ => return Ok(()),
+    };
+    let mismatch = |code: &str, message| {
+        ProviderError::new(ErrorKind::InvalidData, message).with_code(code.to_owned())
+    };
+    if let Some(size) = source.size.filter(|size| *size != written) {
+        return Err(mismatch(
+            "SizeMismatch",
+            format!("Transferred {} bytes instead of {}", written, size),
+        ));
+    }
+    if let Some(size) = object.metadata.size.filter(|size| *size != written) {
+        return Err(mismatch(
+            "SizeMismatch",
+            format!("Destination has {} bytes instead of {}", size, written),
+        ));
+    }
+
+    let checksums = match checksums {
+        Some(checksums) => checksums,
+        None if object.metadata.size.is_none() => {
+            let pause_control = control.clone();
+            let size = retrying(shared, id, control, "verify", || {
+                pausable(slots, pause_control.clone(), || read_size(to))
+            })
+            .await?;
+            if size != written {
+                return Err(mismatch(
+                    "SizeMismatch",
+                    format!("Destination has {} bytes instead of {}", size, written),
+                ));
+            }
+            info!(size, "Verified size of the destination");
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+    // The size of the parts of the source's multipart ETag is unknown
+    let source = Metadata {
+        etag: source.etag.clone().filter(|etag| !etag.contains('-')),
+        ..source.clone()
+    };
+    if checksums.matches(&source) == Some(false) {
+        return Err(mismatch(
+            "ChecksumMismatch",
+            String::from("Data read from the source doesn't match its checksum"),
+        ));
+    }
+    let matches = match checksums.matches(&object.metadata) {
+        Some(matches) => matches,
+        None if object.metadata.encrypted_with_key == Some(true) => {
+            info!("Destination is encrypted with a key, verified its size only");
+            return Ok(());
+        }
+        None => {
+            let pause_control = control.clone();
+            let written = retrying(shared, id, control, "verify", || {
+                pausable(slots, pause_control.clone(), || read_checksums(to, None))
+            })
+            .await?;
+            written.sha256 == checksums.sha256
+        }
+    };
+    if !matches {
+        return Err(mismatch(
+            "ChecksumMismatch",
+            String::from("Data written to the destination doesn't match the data read"),
+        ));
+    }
+    info!(sha256 = %checksums.sha256, "Verified checksums");
+    Ok(())
+}
+
+/// Deletes the partially written destination of a failed or cancelled job
+async fn remove_partial(to: &Location) {
+    match to.provider.delete(&to.path).await {
+        Ok(()) => info!("Removed partially written file"),
+        Err(e) if e.kind() == ErrorKind::NotFound => (),
+        Err(e) => warn!(error = %e, "Couldn't remove partially written file"),
+    }
+}
+
+/// Performs the given job, returning the state it has finished in
+///
+/// Until the source of a move is deleted, the job is abandoned as soon
+/// as it is cancelled.
+async fn perform(
+    shared: &Arc<Shared>,
+    id: JobId,
+    job: &Job,
+    slots: &Slots,
+    mut control: watch::Receiver<Control>,
+) -> Result<JobState, ProviderError> {
+    let started = Instant::now();
+    let (from, to) = match (job.kind, &job.destination) {
+        (JobKind::Delete, _) | (_, None) => {
+            let from = &job.source;
+            retrying(shared, id, &mut control, "delete", || {
+                from.provider.delete(&from.path)
+            })
+            .await?;
+            info!("Deleted");
+            return Ok(JobState::Completed);
+        }
+        (_, Some(to)) => (&job.source, to),
+    };
+    // Writing a file onto itself would destroy it
+    if from.is_same_file(to).await {
+        return Err(ProviderError::new(
+            ErrorKind::InvalidInput,
+            String::from("Source and destination are the same file"),
+        )
+        .with_code(String::from("SameFile")));
+    }
+
+    let object = retrying(shared, id, &mut control, "stat", || {
+        from.provider.stat(&from.path)
+    })
+    .await?;
+    let mut to = to.clone();
+    let existing = retrying(shared, id, &mut control, "stat", || {
+        to.provider.stat(&to.path)
+    })
+    .await;
+    let existed = match existing {
+        Ok(existing) => {
+            let conflict = Conflict {
+                source: object.metadata.clone(),
+                destination: existing.metadata,
+            };
+            let resolution =
+                match checksums_differ(shared, id, &mut control, from, &to, &conflict).await? {
+                    Some(true) => Resolution::Overwrite,
+                    Some(false) => Resolution::Skip,
+                    None => resolve_conflict(shared, id, &mut control, conflict).await?,
+                };
+            match resolution {
+                Resolution::Overwrite => true,
+                Resolution::Skip => {
+                    info!("Skipped, destination already exists");
+                    return Ok(JobState::Skipped);
+                }
+                Resolution::Rename => {
+                    to.path = free_path(shared, id, &mut control, &to).await?;
+                    shared.set_destination(id, to.path.clone());
+                    info!(to = %to.path, "Destination already exists, renamed");
+                    false
+                }
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => false,
+        // Without knowing whether the destination exists, it can't be
+        // treated according to the conflict policy
+        Err(e) => return Err(e),
+    };
+    let to = &to;
+    // Every attempt reads the file anew, and writes it from the start
+    let link_policy = shared.options.link_policy;
+    let capabilities = to.provider.capabilities();
+    let stream_control = control.clone();
+    let attempt = || {
+        let control = stream_control.clone();
+        let kind = object.kind.clone();
+        async move {
+            match open_transfer(from, kind, link_policy).await? {
+                Transfer::Skip => Ok(Written::Nothing),
+                Transfer::Link(target) => {
+                    // Links can't be created over existing files
+                    if existed {
+                        match to.provider.delete(&to.path).await {
+                            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                            _ => (),
+                        }
+                    }
+                    to.provider.create_symlink(&to.path, &target).await?;
+                    Ok(Written::Link)
+                }
+                Transfer::Stream(stream) => {
+                    let size = object
+                        .metadata
+                        .size
+                        .or_else(|| stream.size_hint().1.map(|s| s as u64));
+                    shared.set_size(id, size);
+                    // Providers size the parts of a file by its exact length
+                    let exact = match stream.size_hint() {
+                        (lower, Some(upper)) if lower == upper => Some(upper as u64),
+                        _ => None,
+                    };
+                    let part_size = capabilities.part_size(exact);
+                    let mut stream = ProgressStream::new(stream, control, shared.clone(), id);
+                    let hasher = shared
+                        .options
+                        .verify_checksums
+                        .then(|| Arc::new(Mutex::new(Hasher::new(part_size))));
+                    if let Some(hasher) = &hasher {
+                        stream = stream.with_hasher(hasher.clone());
+                    }
+                    to.provider.write(&to.path, Box::pin(stream)).await?;
+                    let checksums = hasher
+                        .map(|hasher| hasher.lock().expect("Couldn't lock hasher mutex").finish());
+                    if let Some(checksums) = &checksums {
+                        shared.set_checksums(id, checksums.clone());
+                    }
+                    Ok(Written::File {
+                        size: shared.transferred(id),
+                        checksums,
+                    })
+                }
+            }
+        }
+    };
+    // Pausing abandons the attempt in progress, without counting as a retry
+    let written = retrying(shared, id, &mut control, "transfer", || {
+        pausable(slots, stream_control.clone(), attempt)
+    })
+    .await;
+    let written = match written {
+        Ok(Written::Nothing) => {
+            info!("Skipped");
+            return Ok(JobState::Skipped);
+        }
+        Ok(written) if job.kind == JobKind::Move || shared.options.verify_checksums => {
+            let source = &object.metadata;
+            verify(shared, id, &mut control, slots, to, &written, source)
+                .await
+                .map(|_| written)
+        }
+        written => written,
+    };
+    if let Err(e) = written {
+        if !existed {
+            remove_partial(to).await;
+        }
+        return Err(e);
+    }
+    if job.kind == JobKind::Move {
+        // The file has already been transferred, so its source is deleted
+        // even if the job is cancelled meanwhile
+        let (_, mut uncancellable) = watch::channel(Control::Run);
+        let deleted = retrying(shared, id, &mut uncancellable, "delete", || {
+            from.provider.delete(&from.path)
+        })
+        .await;
+        if let Err(e) = deleted {
+            warn!(error = %e, "Couldn't delete the source of a moved file");
+            return Err(ProviderError::new(
+                e.kind(),
+                format!(
+                    "File has been copied to {}, but couldn't be deleted: {}",
+                    to.path,
+                    e.message()
+                ),
+            )
+            .with_code(String::from("SourceNotDeleted")));
+        }
+        info!(elapsed = ?started.elapsed(), "Moved");
+    } else {
+        info!(elapsed = ?started.elapsed(), "Copied");
+    }
+    Ok(JobState::Completed)
+}
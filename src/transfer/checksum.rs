@@ -0,0 +1,206 @@
+//! Module containing the checksums transferred files are verified with
+use std::fmt::Write;
+
+use futures::StreamExt;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use super::Location;
+use crate::providers::{err::ProviderError, Metadata};
+
+/// Checksums of the data of a transferred file
+///
+/// # Examples
+///
+/// ```
+/// use versfm::{providers::Metadata, transfer::Checksums};
+/// let checksums = Checksums::of(b"hello", None);
+/// assert_eq!(checksums.md5, "5d41402abc4b2a76b9719d911017c592");
+/// let metadata = Metadata {
+///     etag: Some(String::from("\"5d41402abc4b2a76b9719d911017c592\"")),
+///     ..Default::default()
+/// };
+/// assert_eq!(checksums.matches(&metadata), Some(true));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksums {
+    /// Hex-encoded MD5 digest, the ETag of objects uploaded to S3 with
+    /// a single request
+    pub md5: String,
+    /// Base64-encoded SHA-256 digest
+    pub sha256: String,
+    /// Base64-encoded CRC32C checksum (in big-endian byte order)
+    pub crc32c: String,
+    /// ETag the file has if it is uploaded in multiple parts of the
+    /// size the checksums were computed for (eg. `<hex>-3`)
+    pub multipart_etag: Option<String>,
+}
+
+impl Checksums {
+    /// Computes the checksums of given data, see `Hasher::new`
+    pub fn of(data: &[u8], part_size: Option<u64>) -> Checksums {
+        let mut hasher = Hasher::new(part_size);
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// Compares the checksums with those reported by a provider in the
+    /// metadata of a file, returns `None` if none of them can be compared
+    ///
+    /// ETags are compared only if they are MD5 digests, or multipart ETags
+    /// of the same number of parts as the multipart ETag of these checksums.
+    pub fn matches(&self, metadata: &Metadata) -> Option<bool> {
+        let mut compared = Vec::new();
+        if let Some(sha256) = &metadata.sha256 {
+            compared.push(*sha256 == self.sha256);
+        }
+        if let Some(crc32c) = &metadata.crc32c {
+            compared.push(*crc32c == self.crc32c);
+        }
+        if let Some(etag) = &metadata.etag {
+            let etag = etag.trim_matches('"').to_ascii_lowercase();
+            match etag.split_once('-') {
+                None if is_md5(&etag) => compared.push(etag == self.md5),
+                Some((digest, parts)) if is_md5(digest) => {
+                    let same_parts = self
+                        .multipart_etag
+                        .as_ref()
+                        .and_then(|m| m.split_once('-'))
+                        .is_some_and(|(_, p)| p == parts);
+                    if same_parts {
+                        compared.push(Some(&etag) == self.multipart_etag.as_ref());
+                    }
+                }
+                _ => (),
+            }
+        }
+        match compared.is_empty() {
+            true => None,
+            false => Some(compared.into_iter().all(|matches| matches)),
+        }
+    }
+}
+
+/// Returns `true` if the given string looks like a hex-encoded MD5 digest
+fn is_md5(s: &str) -> bool {
+    s.len() == 32 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).ok();
+        hex
+    })
+}
+
+/// Computes the MD5 digest of every part of the data
+struct Parts {
+    size: u64,
+    current: Md5,
+    filled: u64,
+    digests: Vec<u8>,
+    count: usize,
+}
+
+impl Parts {
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (self.size - self.filled).min(data.len() as u64) as usize;
+            self.current.update(&data[..take]);
+            self.filled += take as u64;
+            data = &data[take..];
+            if self.filled == self.size {
+                self.finish_part();
+            }
+        }
+    }
+
+    fn finish_part(&mut self) {
+        let current = std::mem::take(&mut self.current);
+        self.digests.extend_from_slice(&current.finalize());
+        self.filled = 0;
+        self.count += 1;
+    }
+
+    fn etag(&self) -> String {
+        let mut parts = Parts {
+            current: self.current.clone(),
+            digests: self.digests.clone(),
+            ..*self
+        };
+        if parts.filled > 0 || parts.count == 0 {
+            parts.finish_part();
+        }
+        format!("{}-{}", to_hex(&Md5::digest(&parts.digests)), parts.count)
+    }
+}
+
+/// Computes the checksums of data fed to it chunk by chunk
+pub(super) struct Hasher {
+    md5: Md5,
+    sha256: Sha256,
+    crc32c: u32,
+    parts: Option<Parts>,
+}
+
+impl Hasher {
+    /// Creates a hasher, which also computes the multipart ETag of the
+    /// data if the size of its parts is given
+    pub(super) fn new(part_size: Option<u64>) -> Hasher {
+        Hasher {
+            md5: Md5::new(),
+            sha256: Sha256::new(),
+            crc32c: 0,
+            parts: part_size.filter(|size| *size > 0).map(|size| Parts {
+                size,
+                current: Md5::new(),
+                filled: 0,
+                digests: Vec::new(),
+                count: 0,
+            }),
+        }
+    }
+
+    pub(super) fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha256.update(data);
+        self.crc32c = crc32c::crc32c_append(self.crc32c, data);
+        if let Some(parts) = &mut self.parts {
+            parts.update(data);
+        }
+    }
+
+    /// Returns the checksums of the data fed to the hasher so far
+    pub(super) fn finish(&self) -> Checksums {
+        Checksums {
+            md5: to_hex(&self.md5.clone().finalize()),
+            sha256: base64::encode(self.sha256.clone().finalize()),
+            crc32c: base64::encode(self.crc32c.to_be_bytes()),
+            multipart_etag: self.parts.as_ref().map(Parts::etag),
+        }
+    }
+}
+
+/// Reads the file under given location, computing its checksums, see
+/// `Hasher::new`
+pub(super) async fn read_checksums(
+    location: &Location,
+    part_size: Option<u64>,
+) -> Result<Checksums, ProviderError> {
+    let mut stream = location.provider.read(&location.path).await?;
+    let mut hasher = Hasher::new(part_size);
+    while let Some(chunk) = stream.next().await {
+        hasher.update(&chunk?);
+    }
+    Ok(hasher.finish())
+}
+
+/// Reads the file under given location, counting its bytes
+pub(super) async fn read_size(location: &Location) -> Result<u64, ProviderError> {
+    let mut stream = location.provider.read(&location.path).await?;
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        size += chunk?.len() as u64;
+    }
+    Ok(size)
+}
@@ -0,0 +1,143 @@
+//! Module containing the policy conflicts between transferred files and
+//! the files already present in their destination are resolved with
+use std::{path::Path, str::FromStr};
+
+use crate::{path::VirtualPath, providers::Metadata};
+
+/// Enum representing how a file that already exists in the destination
+/// of a copy or move should be treated
+///
+/// * `Ask` - The user is asked what to do with the file
+/// * `Overwrite` - The file is overwritten
+/// * `Skip` - The file is kept and nothing is transferred
+/// * `Rename` - The file is kept and the transferred file is written
+///   next to it, under its name with a numbered suffix (eg. `notes (1).txt`)
+/// * `OverwriteIfNewer` - The file is overwritten only if the transferred
+///   file is known to have been modified after it, and kept otherwise
+/// * `OverwriteIfDifferent` - The file is overwritten unless both files
+///   are known to have the same size (and ETag, if both of them have one).
+///   When the engine verifies checksums, files of the same size are also
+///   compared by their checksums, computed by reading the files that
+///   don't report any
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    #[default]
+    Ask,
+    Overwrite,
+    Skip,
+    Rename,
+    OverwriteIfNewer,
+    OverwriteIfDifferent,
+}
+
+impl ConflictPolicy {
+    /// Decides what should be done with a file that already exists in
+    /// the destination, returns `None` if the user should be asked
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use versfm::{
+    ///     providers::Metadata,
+    ///     transfer::{Conflict, ConflictPolicy, Resolution},
+    /// };
+    /// let conflict = Conflict {
+    ///     source: Metadata { size: Some(10), ..Default::default() },
+    ///     destination: Metadata { size: Some(10), ..Default::default() },
+    /// };
+    /// let policy = ConflictPolicy::OverwriteIfDifferent;
+    /// assert_eq!(policy.resolve(&conflict), Some(Resolution::Skip));
+    /// assert_eq!(ConflictPolicy::Ask.resolve(&conflict), None);
+    /// ```
+    pub fn resolve(&self, conflict: &Conflict) -> Option<Resolution> {
+        let (source, destination) = (&conflict.source, &conflict.destination);
+        let overwrite = match self {
+            ConflictPolicy::Ask => return None,
+            ConflictPolicy::Overwrite => return Some(Resolution::Overwrite),
+            ConflictPolicy::Skip => return Some(Resolution::Skip),
+            ConflictPolicy::Rename => return Some(Resolution::Rename),
+            ConflictPolicy::OverwriteIfNewer => source
+                .modified
+                .zip(destination.modified)
+                .is_some_and(|(source, destination)| source > destination),
+            ConflictPolicy::OverwriteIfDifferent => {
+                let sizes = source.size.zip(destination.size).map(|(s, d)| s != d);
+                let etags = source
+                    .etag
+                    .as_ref()
+                    .zip(destination.etag.as_ref())
+                    .map(|(s, d)| s != d);
+                match (sizes, etags) {
+                    (None, None) => true,
+                    (sizes, etags) => sizes.unwrap_or(false) || etags.unwrap_or(false),
+                }
+            }
+        };
+        match overwrite {
+            true => Some(Resolution::Overwrite),
+            false => Some(Resolution::Skip),
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(ConflictPolicy::Ask),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "if-newer" => Ok(ConflictPolicy::OverwriteIfNewer),
+            "if-different" => Ok(ConflictPolicy::OverwriteIfDifferent),
+            _ => Err(format!(
+                "Invalid conflict policy '{}' [Options: \"ask\", \"overwrite\", \"skip\", \"rename\", \"if-newer\", \"if-different\"]",
+                s
+            )),
+        }
+    }
+}
+
+impl From<Resolution> for ConflictPolicy {
+    fn from(resolution: Resolution) -> Self {
+        match resolution {
+            Resolution::Overwrite => ConflictPolicy::Overwrite,
+            Resolution::Skip => ConflictPolicy::Skip,
+            Resolution::Rename => ConflictPolicy::Rename,
+        }
+    }
+}
+
+/// Enum representing what is done with a file that already exists in
+/// the destination, see `ConflictPolicy` for the meaning of the variants
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+/// Metadata of a transferred file and of the file already present in
+/// its destination
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub source: Metadata,
+    pub destination: Metadata,
+}
+
+/// Returns the given path with the `n`-th numbered suffix appended to
+/// the file name, before its extension
+pub(super) fn with_suffix(path: &VirtualPath, n: u32) -> VirtualPath {
+    let name = Path::new(path.file_name().unwrap_or_default());
+    let mut renamed = name.file_stem().unwrap_or_default().to_owned();
+    renamed.push(format!(" ({})", n));
+    if let Some(extension) = name.extension() {
+        renamed.push(".");
+        renamed.push(extension);
+    }
+    match path.parent() {
+        Some(parent) => parent.join(renamed),
+        None => path.clone(),
+    }
+}
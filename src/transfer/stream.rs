@@ -0,0 +1,196 @@
+//! Module containing the stream the engine passes transferred files
+//! through to providers
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::Bytes;
+use futures::{future, Future, Stream, StreamExt};
+use tokio::sync::watch;
+use tracing::info;
+
+use super::{checksum::Hasher, Control, JobId, Shared, Slots, TransferEvent, PROGRESS_INTERVAL};
+use crate::providers::{
+    err::{ErrorKind, ProviderError},
+    BoxedByteStream,
+};
+
+/// Returns the error operations of cancelled jobs fail with
+pub(super) fn cancelled_error() -> ProviderError {
+    ProviderError::new(
+        ErrorKind::Other,
+        String::from("Transfer has been cancelled"),
+    )
+    .with_code(String::from("Cancelled"))
+}
+
+/// Waits until the job is cancelled
+async fn cancelled(control: &mut watch::Receiver<Control>) {
+    loop {
+        if *control.borrow_and_update() == Control::Cancel {
+            return;
+        }
+        if control.changed().await.is_err() {
+            future::pending::<()>().await;
+        }
+    }
+}
+
+/// Performs the given operation, unless the job is cancelled first
+pub(super) async fn cancellable<T>(
+    control: &mut watch::Receiver<Control>,
+    operation: impl Future<Output = Result<T, ProviderError>>,
+) -> Result<T, ProviderError> {
+    tokio::select! {
+        result = operation => result,
+        _ = cancelled(control) => Err(cancelled_error()),
+    }
+}
+
+/// Waits until the job is paused or cancelled
+pub(super) async fn interrupted(control: &mut watch::Receiver<Control>) {
+    loop {
+        if *control.borrow_and_update() != Control::Run {
+            return;
+        }
+        if control.changed().await.is_err() {
+            future::pending::<()>().await;
+        }
+    }
+}
+
+/// Performs the operation created by `operation`, unless the job is
+/// cancelled first
+///
+/// The operation is abandoned as soon as the job is paused, so it doesn't
+/// keep any requests open (eg. S3 closes requests idle for ~20s), and is
+/// started anew once the job is resumed. While the job is paused its
+/// `slots` are given back.
+pub(super) async fn pausable<T, F, Fut>(
+    slots: &Slots,
+    mut control: watch::Receiver<Control>,
+    mut operation: F,
+) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    loop {
+        if slots.wait_while_paused(&mut control).await? {
+            info!("Resumed, starting over");
+        }
+        tokio::select! {
+            result = operation() => return result,
+            _ = interrupted(&mut control) => {
+                if *control.borrow() == Control::Pause {
+                    info!("Paused, abandoned the operation in progress");
+                }
+            }
+        }
+    }
+}
+
+/// Wraps given stream into one that fails as soon as the job is cancelled
+fn controlled(
+    stream: Pin<BoxedByteStream>,
+    control: watch::Receiver<Control>,
+) -> impl Stream<Item = Result<Bytes, io::Error>> + Send {
+    futures::stream::unfold(Some((stream, control)), |state| async move {
+        let (mut stream, mut control) = state?;
+        tokio::select! {
+            chunk = stream.next() => chunk.map(|chunk| (chunk, Some((stream, control)))),
+            _ = cancelled(&mut control) => {
+                let error = io::Error::other("Transfer has been cancelled");
+                Some((Err(error), None))
+            }
+        }
+    })
+}
+
+/// Stream passing through the chunks of the transferred file, while
+/// recording how many bytes of it have been transferred
+pub(super) struct ProgressStream {
+    stream: Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>,
+    size_hint: (usize, Option<usize>),
+    shared: Arc<Shared>,
+    id: JobId,
+    transferred: u64,
+    reported: Option<Instant>,
+    hasher: Option<Arc<Mutex<Hasher>>>,
+}
+
+impl ProgressStream {
+    pub(super) fn new(
+        stream: Pin<BoxedByteStream>,
+        control: watch::Receiver<Control>,
+        shared: Arc<Shared>,
+        id: JobId,
+    ) -> ProgressStream {
+        ProgressStream {
+            size_hint: stream.size_hint(),
+            stream: Box::pin(controlled(stream, control)),
+            shared,
+            id,
+            transferred: 0,
+            reported: None,
+            hasher: None,
+        }
+    }
+
+    /// Feeds the chunks passed through the stream to given hasher
+    pub(super) fn with_hasher(mut self, hasher: Arc<Mutex<Hasher>>) -> ProgressStream {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Records the current progress, reporting it if enough time has
+    /// passed since it was last reported or the stream has ended
+    fn record(&mut self, ended: bool) {
+        self.shared.set_transferred(self.id, self.transferred);
+        if ended
+            || self
+                .reported
+                .is_none_or(|r| r.elapsed() >= PROGRESS_INTERVAL)
+        {
+            self.reported = Some(Instant::now());
+            self.shared
+                .events
+                .send(TransferEvent::Progress {
+                    id: self.id,
+                    transferred: self.transferred,
+                })
+                .ok();
+        }
+    }
+}
+
+impl Stream for ProgressStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.stream.as_mut().poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(hasher) = &self.hasher {
+                    hasher
+                        .lock()
+                        .expect("Couldn't lock hasher mutex")
+                        .update(chunk);
+                }
+                self.transferred += chunk.len() as u64;
+                self.record(false);
+            }
+            Poll::Ready(None) => self.record(true),
+            _ => (),
+        }
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.size_hint
+    }
+}
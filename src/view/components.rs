@@ -1,28 +1,33 @@
 //! Module defining components that are later used when
 //! composing screens
-use std::{pin::Pin, sync::MutexGuard};
+use std::{
+    ffi::{OsStr, OsString},
+    sync::{Arc, MutexGuard},
+};
 
 use async_trait::async_trait;
 
 pub mod err;
-mod filesystem_list;
-mod s3_list;
+mod provider_list;
 
-pub use filesystem_list::FilesystemList;
-pub use s3_list::S3List;
+pub use provider_list::ProviderList;
 use tui::{
     style::{Color, Modifier, Style},
     widgets::{Block, Borders, List, ListItem, ListState},
 };
 
-use crate::providers::{BoxedByteStream, Kind};
+use crate::{
+    path::VirtualPath,
+    providers::{Capabilities, Kind, Metadata, Object, Provider},
+};
 
 use self::err::ComponentError;
 
 /// Enum representing various selection types an entry can be in
-#[derive(Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     Unselected,
+    Queued,
     Processed,
     ToMove,
     ToDelete,
@@ -38,7 +43,7 @@ pub struct SelectableEntry<T> {
 impl<T> SelectableEntry<T> {
     fn new(value: T) -> SelectableEntry<T> {
         SelectableEntry {
-            value: value,
+            value,
             state: State::Unselected,
         }
     }
@@ -56,7 +61,7 @@ impl<T> SelectableEntry<T> {
     /// Selects the entry currently under cursor giving
     /// it the state provided in `new` function argument
     fn select(&mut self, new: State) {
-        if new == State::Processed {
+        if matches!(new, State::Queued | State::Processed) {
             self.state = new;
             return;
         }
@@ -67,22 +72,46 @@ impl<T> SelectableEntry<T> {
     }
 }
 
-/// Struct containing a filename, and an information whether
-/// the file is a directory, or a regular file
+/// Struct containing a filename, an information whether
+/// the file is a directory, or a regular file and its metadata
 #[derive(Clone)]
 pub struct FilenameEntry {
-    file_name: String,
+    file_name: OsString,
     kind: Kind,
+    metadata: Metadata,
 }
 
 impl FilenameEntry {
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &OsStr {
         &self.file_name
     }
 
     pub fn kind(&self) -> &Kind {
         &self.kind
     }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Creates an entry representing a regular file with unknown metadata
+    fn file(file_name: &OsStr) -> FilenameEntry {
+        FilenameEntry {
+            file_name: file_name.to_owned(),
+            kind: Kind::File,
+            metadata: Metadata::default(),
+        }
+    }
+}
+
+impl From<Object> for FilenameEntry {
+    fn from(object: Object) -> Self {
+        FilenameEntry {
+            file_name: object.name,
+            kind: object.kind,
+            metadata: object.metadata,
+        }
+    }
 }
 
 pub trait StatefulContainer {
@@ -105,13 +134,13 @@ pub trait SelectableContainer<T> {
 }
 
 pub trait ASelectableFilenameList:
-    StatefulContainer + SelectableContainer<String> + Sync + Send
+    StatefulContainer + SelectableContainer<OsString> + Sync + Send
 {
-    fn lock_items(&self) -> MutexGuard<Vec<SelectableEntry<FilenameEntry>>>;
-    fn lock_state(&self) -> MutexGuard<ListState>;
+    fn lock_items(&self) -> MutexGuard<'_, Vec<SelectableEntry<FilenameEntry>>>;
+    fn lock_state(&self) -> MutexGuard<'_, ListState>;
 
     /// Gets all the items that have a given selection type
-    fn get_name_of_selected(&self) -> Option<String> {
+    fn get_name_of_selected(&self) -> Option<OsString> {
         let items = self.lock_items();
         let state = self.lock_state();
         if let Some(i) = state.selected() {
@@ -132,7 +161,7 @@ pub trait ASelectableFilenameList:
     /// # Arguments
     /// * `file_name` - File name of the item the state should be set for
     /// * `state` - New state that should be set for the item
-    fn set_item_state_by_filename(&self, file_name: &str, state: State) {
+    fn set_item_state_by_filename(&self, file_name: &OsStr, state: State) {
         let mut items = self.lock_items();
         if let Some(item) = items.iter_mut().find(|v| v.value().name() == file_name) {
             item.select(state);
@@ -143,7 +172,7 @@ pub trait ASelectableFilenameList:
     ///
     /// # Arguments
     /// * `file_name` - Filename of the item to obtain
-    fn get_item_by_filename(&self, file_name: &str) -> Option<FilenameEntry> {
+    fn get_item_by_filename(&self, file_name: &OsStr) -> Option<FilenameEntry> {
         let mut items = self.lock_items();
         if let Some(item) = items.iter_mut().find(|v| v.value().name() == file_name) {
             return Some(item.value().clone());
@@ -155,7 +184,7 @@ pub trait ASelectableFilenameList:
     ///
     /// # Arguments
     /// * `file_name` - Filename of the item to delete
-    fn remove_element_of_filename(&self, file_name: &str) {
+    fn remove_element_of_filename(&self, file_name: &OsStr) {
         let mut items = self.lock_items();
         let mut state = self.lock_state();
         if let Some((element_index, _)) = items
@@ -177,14 +206,24 @@ pub trait ASelectableFilenameList:
     /// Adds a new element to the list
     ///
     /// # Arguments
-    /// * `file_name` - Filename of the element to add
-    fn add_new_element(&self, file_name: &str) {
-        if self.get_item_by_filename(file_name).is_none() {
+    /// * `entry` - The element to add
+    fn add_new_element(&self, entry: FilenameEntry) {
+        if self.get_item_by_filename(entry.name()).is_none() {
             let mut items = self.lock_items();
-            items.push(SelectableEntry::new(FilenameEntry {
-                file_name: file_name.to_owned(),
-                kind: Kind::File,
-            }));
+            items.push(SelectableEntry::new(entry));
+        }
+    }
+
+    /// Adds a new element to the list, or replaces the element of the
+    /// same filename, keeping its selection state
+    ///
+    /// # Arguments
+    /// * `entry` - The element to add
+    fn add_or_replace_element(&self, entry: FilenameEntry) {
+        let mut items = self.lock_items();
+        match items.iter_mut().find(|v| v.value().name() == entry.name()) {
+            Some(item) => item.value = entry,
+            None => items.push(SelectableEntry::new(entry)),
         }
     }
 }
@@ -201,7 +240,7 @@ impl<T: ASelectableFilenameList> StatefulContainer for T {
     fn next(&self) {
         let items = self.lock_items();
         let mut state = self.lock_state();
-        if items.len() > 0 {
+        if !items.is_empty() {
             let i = match state.selected() {
                 Some(i) => {
                     if i >= items.len() - 1 {
@@ -220,7 +259,7 @@ impl<T: ASelectableFilenameList> StatefulContainer for T {
     fn previous(&self) {
         let items = self.lock_items();
         let mut state = self.lock_state();
-        if items.len() > 0 {
+        if !items.is_empty() {
             let i = match state.selected() {
                 Some(i) => {
                     if i == 0 {
@@ -237,23 +276,25 @@ impl<T: ASelectableFilenameList> StatefulContainer for T {
     }
 }
 
-impl<T: ASelectableFilenameList> SelectableContainer<String> for T {
+impl<T: ASelectableFilenameList> SelectableContainer<OsString> for T {
     fn select(&self, selection: State) {
         let mut items = self.lock_items();
         match self.get_current().selected() {
             None => (),
-            Some(i) => {
-                if items.len() > i {
-                    match items[i].value().kind() {
-                        Kind::File => items[i].select(selection),
-                        Kind::Directory | Kind::Unknown => (),
-                    }
-                }
-            }
+            Some(i) if items.len() > i => match items[i].value().kind() {
+                Kind::File | Kind::Symlink { .. } => items[i].select(selection),
+                Kind::Directory
+                | Kind::Fifo
+                | Kind::Socket
+                | Kind::BlockDevice
+                | Kind::CharDevice
+                | Kind::Unknown => (),
+            },
+            Some(_) => (),
         };
     }
 
-    fn get_selected(&self, selection: State) -> Vec<String> {
+    fn get_selected(&self, selection: State) -> Vec<OsString> {
         self.lock_items()
             .iter()
             .filter(|i| *i.selected() == selection)
@@ -262,56 +303,58 @@ impl<T: ASelectableFilenameList> SelectableContainer<String> for T {
     }
 }
 
+#[async_trait]
 pub trait Navigatable {
     /// Moves into the directory currently under cursor
-    fn move_into_selected_dir(&self);
+    ///
+    /// The directory is listed before moving into it, so if listing
+    /// fails the current directory stays unchanged
+    async fn move_into_selected_dir(&self) -> Result<(), ComponentError>;
     /// Moves out of the current directory
-    fn move_out_of_selected_dir(&self);
+    ///
+    /// The parent directory is listed before moving into it, so if
+    /// listing fails the current directory stays unchanged
+    async fn move_out_of_selected_dir(&self) -> Result<(), ComponentError>;
     /// Returns the path of the current directory
-    fn get_current_path(&self) -> String;
+    fn get_current_path(&self) -> VirtualPath;
 }
 
 #[async_trait]
 pub trait FileCRUD {
+    /// Lists the current directory anew, cached listings may be used
     async fn refresh(&self) -> Result<(), ComponentError>;
-    /// Signifies that the processing of a given item has begun
-    ///
-    /// # Arguments:
+    /// Lists the current directory anew, bypassing cached listings
+    async fn reload(&self) -> Result<(), ComponentError>;
+    /// Updates the entry of the file under given path, after the file
+    /// has been changed by something else than this list (eg. a transfer)
     ///
-    /// * `file_name` - Name of the file that has started to be processed
-    fn start_processing_item(&self, file_name: &str);
-    /// Signifies that the processing of a given item has stopped
+    /// The entry is added if the file has been created, replaced with
+    /// the file's current metadata if it has been overwritten, and
+    /// removed if it no longer exists. Files outside of the current
+    /// directory are ignored
     ///
     /// # Arguments:
     ///
-    /// * `file_name` - Name of the file that is no longer processed
-    fn stop_processing_item(&self, file_name: &str);
-    /// Obtains the file stream of the file with given filename
+    /// * `path` - Path of the changed file
+    async fn refresh_entry(&self, path: &VirtualPath);
+    /// Signifies that the given item is waiting to be processed
     ///
     /// # Arguments:
     ///
-    /// * `file_name` - Name of the file the stream should be obtained for
-    async fn get_file_stream(
-        &self,
-        file_name: &str,
-    ) -> Result<Pin<BoxedByteStream>, ComponentError>;
-    /// Saves given file from the provided file stream
+    /// * `file_name` - Name of the file that is waiting to be processed
+    fn queue_item(&self, file_name: &OsStr);
+    /// Signifies that the processing of a given item has begun
     ///
     /// # Arguments:
     ///
-    /// * `file_name` - Filename of the new file
-    /// * `stream` - File Stream used to create the file
-    async fn put_file(
-        &self,
-        file_name: &str,
-        stream: Pin<BoxedByteStream>,
-    ) -> Result<(), ComponentError>;
-    /// Deletes file of given filename
+    /// * `file_name` - Name of the file that has started to be processed
+    fn start_processing_item(&self, file_name: &OsStr);
+    /// Signifies that the processing of a given item has stopped
     ///
     /// # Arguments:
     ///
-    /// * `file_name` - Filename of the file to be deleted
-    async fn delete_file(&self, file_name: &str) -> Result<(), ComponentError>;
+    /// * `file_name` - Name of the file that is no longer processed
+    fn stop_processing_item(&self, file_name: &OsStr);
     /// Return the name of the resource FileCRUD is implemented over
     ///
     /// eg. name of the S3 bucket
@@ -320,6 +363,14 @@ pub trait FileCRUD {
     ///
     /// eg. 'S3', 'local', 'onedrive', etc.
     fn get_provider_name(&self) -> &str;
+    /// Return the capabilities of the provider FileCRUD is implemented over
+    fn get_capabilities(&self) -> Capabilities;
+    /// Return the provider FileCRUD is implemented over, so files can be
+    /// transferred to and from it
+    ///
+    /// Files are transferred by submitting jobs operating on the provider
+    /// to a `TransferEngine`, so every transfer is performed the same way
+    fn get_provider(&self) -> Arc<dyn Provider>;
 }
 
 pub trait TuiListDisplay {
@@ -328,12 +379,12 @@ pub trait TuiListDisplay {
     /// # Arguments
     ///
     /// * `is_focused` - signifies whether the list that is
-    /// generated is currently focused
-    fn make_list(&self, is_focused: bool) -> List;
+    ///   generated is currently focused
+    fn make_list(&self, is_focused: bool) -> List<'_>;
 }
 
 impl<T: ASelectableFilenameList + FileCRUD + Navigatable> TuiListDisplay for T {
-    fn make_list(&self, is_focused: bool) -> List {
+    fn make_list(&self, is_focused: bool) -> List<'_> {
         let mut style = Style::default().fg(Color::White);
         if is_focused {
             style = style.fg(Color::LightBlue);
@@ -343,7 +394,7 @@ impl<T: ASelectableFilenameList + FileCRUD + Navigatable> TuiListDisplay for T {
                 "{}@{}:{}",
                 self.get_resource_name(),
                 self.get_provider_name(),
-                self.get_current_path()
+                self.get_current_path().path_str()
             ))
             .style(style)
             .borders(Borders::ALL);
@@ -359,18 +410,40 @@ impl<T: ASelectableFilenameList + FileCRUD + Navigatable> TuiListDisplay for T {
 /// Helper function used to stylize the filename entries based on their properties
 ///
 /// * `options` - A mutex guard to the list of selectable filename entries from which
-/// to create the stylized list items
-fn transform_list(options: MutexGuard<Vec<SelectableEntry<FilenameEntry>>>) -> Vec<ListItem> {
+///   to create the stylized list items
+fn transform_list(
+    options: MutexGuard<'_, Vec<SelectableEntry<FilenameEntry>>>,
+) -> Vec<ListItem<'_>> {
     options
         .iter()
         .map(|o| {
-            let mut text = o.value().name().to_owned();
+            let mut text = o.value().name().to_string_lossy().into_owned();
             let mut style = Style::default();
 
             match o.value().kind() {
                 Kind::Directory => style = style.add_modifier(Modifier::ITALIC),
+                Kind::Symlink {
+                    target,
+                    is_dir,
+                    broken,
+                } => {
+                    style = style.fg(if *broken { Color::Red } else { Color::Cyan });
+                    if *is_dir {
+                        style = style.add_modifier(Modifier::ITALIC);
+                    }
+                    text.push_str(" -> ");
+                    text.push_str(&target.to_string_lossy());
+                }
+                Kind::Fifo | Kind::Socket | Kind::BlockDevice | Kind::CharDevice => {
+                    style = style.fg(Color::Yellow);
+                    text.push_str(match o.value().kind() {
+                        Kind::Fifo => " |",
+                        Kind::Socket => " =",
+                        _ => " #",
+                    });
+                }
                 Kind::Unknown => style = style.fg(Color::DarkGray),
-                _ => (),
+                Kind::File => (),
             };
             match o.selected() {
                 State::ToMove => {
@@ -385,6 +458,10 @@ fn transform_list(options: MutexGuard<Vec<SelectableEntry<FilenameEntry>>>) -> V
                     style = style.bg(Color::Green);
                     text.push_str(" [C]");
                 }
+                State::Queued => {
+                    style = style.fg(Color::DarkGray);
+                    text.push_str(" [.]");
+                }
                 State::Processed => {
                     style = style.bg(Color::DarkGray);
                     text.push_str(" [/]");
@@ -1,5 +1,10 @@
 //! Module that defines view errors
-use std::fmt::{self};
+use std::{
+    error::Error,
+    fmt::{self},
+};
+
+use crate::providers::err::{ErrorKind, ProviderError};
 
 /// Error struct used in components
 #[derive(Debug, Clone)]
@@ -7,6 +12,7 @@ pub struct ComponentError {
     component: String,
     code: String,
     message: String,
+    source: Option<ProviderError>,
 }
 
 impl ComponentError {
@@ -15,6 +21,34 @@ impl ComponentError {
             component,
             message,
             code,
+            source: None,
+        }
+    }
+
+    /// Maps given ProviderError to a ComponentError
+    ///
+    /// * `component` - Name of the component the error ocurred in
+    /// * `err` - ProviderError to map
+    /// * `file` - OPTIONAL path to the file which caused the error
+    pub fn from_provider_error(
+        component: &str,
+        err: ProviderError,
+        file: Option<&str>,
+    ) -> ComponentError {
+        let mut message = match file {
+            None => err.message().to_owned(),
+            Some(file_name) => format!("(File: {}) {}", file_name, err.message()),
+        };
+        let mut cause = err.source();
+        while let Some(c) = cause {
+            message.push_str(&format!(" - {}", c));
+            cause = c.source();
+        }
+        ComponentError {
+            component: component.to_owned(),
+            code: err.code().to_owned(),
+            message,
+            source: Some(err),
         }
     }
 
@@ -29,6 +63,17 @@ impl ComponentError {
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    /// Returns the kind of the provider error that caused this error,
+    /// or `None` if the error didn't originate in a provider
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.source.as_ref().map(|e| e.kind())
+    }
+
+    /// Returns `true` if repeating the failed operation may succeed
+    pub fn is_retryable(&self) -> bool {
+        self.source.as_ref().is_some_and(|e| e.is_retryable())
+    }
 }
 
 impl fmt::Display for ComponentError {
@@ -40,3 +85,9 @@ impl fmt::Display for ComponentError {
         )
     }
 }
+
+impl Error for ComponentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| e as &(dyn Error + 'static))
+    }
+}
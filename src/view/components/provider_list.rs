@@ -0,0 +1,188 @@
+use std::{
+    ffi::OsStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use tui::widgets::ListState;
+
+use crate::{
+    path::VirtualPath,
+    providers::{
+        err::{ErrorKind, ProviderError},
+        retry::RetryPolicy,
+        Capabilities, Provider,
+    },
+};
+
+use super::{
+    err::ComponentError, ASelectableFilenameList, FileCRUD, FilenameEntry, Navigatable,
+    SelectableEntry, State, StatefulContainer,
+};
+
+/// Interactive list of entries representing files exposed by a provider
+pub struct ProviderList<P: Provider> {
+    provider: Arc<P>,
+    curr_path: Mutex<VirtualPath>,
+    items: Arc<Mutex<Vec<SelectableEntry<FilenameEntry>>>>,
+    state: Arc<Mutex<ListState>>,
+    retry: RetryPolicy,
+}
+
+impl<P: Provider> ProviderList<P> {
+    pub fn new(provider: P) -> ProviderList<P> {
+        let curr_path = provider.initial_path();
+        ProviderList {
+            provider: Arc::new(provider),
+            curr_path: Mutex::new(curr_path),
+            items: Arc::new(Mutex::new(Vec::new())),
+            state: Arc::new(Mutex::new(ListState::default())),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Sets the policy failed listings are retried according to
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> ProviderList<P> {
+        self.retry = retry;
+        self
+    }
+
+    fn lock_curr_path(&self) -> MutexGuard<'_, VirtualPath> {
+        self.curr_path
+            .lock()
+            .expect("Couldn't lock curr_path mutex")
+    }
+
+    /// Lists the directory under given path and, if that succeeds,
+    /// makes it the current directory
+    async fn load(&self, path: VirtualPath) -> Result<(), ComponentError> {
+        let files = self
+            .retry
+            .run("list", || self.provider.list(&path))
+            .await
+            .map_err(|e| self.handle_error(e, Some(&path)))?;
+        let changed_dir = {
+            let mut curr_path = self.lock_curr_path();
+            let changed_dir = *curr_path != path;
+            *curr_path = path;
+            changed_dir
+        };
+        *self.lock_items() = files
+            .into_iter()
+            .map(|i| SelectableEntry::new(FilenameEntry::from(i)))
+            .collect();
+        if changed_dir {
+            self.clear_state();
+        }
+        Ok(())
+    }
+
+    /// Maps given ProviderError to a ComponentError
+    ///
+    /// * `err` - ProviderError to map
+    /// * `file` - OPTIONAL path to the file which caused the error
+    fn handle_error(&self, err: ProviderError, file: Option<&VirtualPath>) -> ComponentError {
+        ComponentError::from_provider_error(
+            self.provider.provider_name(),
+            err,
+            file.map(|f| f.to_string()).as_deref(),
+        )
+    }
+}
+
+impl<P: Provider> ASelectableFilenameList for ProviderList<P> {
+    fn lock_items(&self) -> MutexGuard<'_, Vec<SelectableEntry<FilenameEntry>>> {
+        self.items.lock().expect("Couldn't lock items mutex")
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, ListState> {
+        self.state.lock().expect("Couldn't lock state mutex")
+    }
+}
+
+#[async_trait]
+impl<P: Provider> Navigatable for ProviderList<P> {
+    async fn move_into_selected_dir(&self) -> Result<(), ComponentError> {
+        let selected = {
+            let items = self.lock_items();
+            let state = self.lock_state();
+            state
+                .selected()
+                .and_then(|i| items.get(i))
+                .map(|i| i.value().clone())
+        };
+        match selected {
+            Some(FilenameEntry {
+                file_name, kind, ..
+            }) if kind.is_navigable() => self.load(self.get_current_path().join(&file_name)).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn move_out_of_selected_dir(&self) -> Result<(), ComponentError> {
+        let parent = self.get_current_path().parent();
+        match parent {
+            Some(parent) => self.load(parent).await,
+            None => Ok(()),
+        }
+    }
+
+    fn get_current_path(&self) -> VirtualPath {
+        self.lock_curr_path().clone()
+    }
+}
+
+#[async_trait]
+impl<P: Provider + 'static> FileCRUD for ProviderList<P> {
+    fn get_resource_name(&self) -> &str {
+        self.provider.resource_name()
+    }
+
+    fn get_provider_name(&self) -> &str {
+        self.provider.provider_name()
+    }
+
+    fn get_capabilities(&self) -> Capabilities {
+        self.provider.capabilities()
+    }
+
+    fn get_provider(&self) -> Arc<dyn Provider> {
+        self.provider.clone()
+    }
+
+    fn queue_item(&self, file_name: &OsStr) {
+        self.set_item_state_by_filename(file_name, State::Queued);
+    }
+
+    fn start_processing_item(&self, file_name: &OsStr) {
+        self.set_item_state_by_filename(file_name, State::Processed);
+    }
+
+    fn stop_processing_item(&self, file_name: &OsStr) {
+        self.set_item_state_by_filename(file_name, State::Unselected);
+    }
+
+    async fn refresh_entry(&self, path: &VirtualPath) {
+        let file_name = match path.file_name() {
+            Some(file_name) if path.parent().as_ref() == Some(&self.get_current_path()) => {
+                file_name
+            }
+            _ => return,
+        };
+        match self.provider.stat(path).await {
+            Ok(object) => self.add_or_replace_element(FilenameEntry::from(object)),
+            Err(e) if e.kind() == ErrorKind::NotFound => self.remove_element_of_filename(file_name),
+            Err(_) => self.add_new_element(FilenameEntry::file(file_name)),
+        }
+    }
+
+    async fn refresh(&self) -> Result<(), ComponentError> {
+        self.load(self.get_current_path()).await
+    }
+
+    async fn reload(&self) -> Result<(), ComponentError> {
+        let path = self.get_current_path();
+        self.provider.invalidate(&path);
+        self.load(path).await
+    }
+}
@@ -1,4 +1,20 @@
 //! Module defining applications screens (views)
+mod action;
 mod dual_pane_list;
+mod progress;
 
+pub use action::{Action, CONFLICT_KEY_HINTS, JOB_KEY_HINTS, KEY_HINTS};
 pub use dual_pane_list::DualPaneList;
+
+/// Enum representing the events screens react to
+///
+/// * `Input` - Input provided by the user
+/// * `StateChanged` - State displayed by the screen has changed (eg. a
+///   background task has finished), so the screen should be redrawn
+/// * `Shutdown` - The application should shut down
+#[derive(Debug, Clone)]
+pub enum Event<I> {
+    Input(I),
+    StateChanged,
+    Shutdown,
+}
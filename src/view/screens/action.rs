@@ -0,0 +1,126 @@
+//! Module defining the actions screens can perform and the default
+//! mapping of keys onto them
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::{transfer::Resolution, view::components::State};
+
+/// Enum representing the actions the user can perform on a screen
+///
+/// * `Confirm` - Carries out the marked operations, or dismisses errors
+///   if there are any
+/// * `EnterDir` - Moves into the selected directory
+/// * `LeaveDir` - Moves out of the current directory
+/// * `Next` - Selects the next entry of the focused list
+/// * `Previous` - Selects the previous entry of the focused list
+/// * `FocusLeft` - Focuses the left list
+/// * `FocusRight` - Focuses the right list
+/// * `Mark` - Marks the selected entry with given state (or unmarks
+///   it if it already is in that state)
+/// * `Refresh` - Refreshes the lists
+/// * `ToggleJobs` - Switches between the lists and the list of jobs
+/// * `PauseJob` - Pauses the selected job, or resumes it if it is paused
+/// * `CancelJob` - Cancels the selected job
+/// * `PauseAllJobs` - Pauses all of the jobs, or resumes them if none
+///   of them is queued or running
+/// * `CancelAllJobs` - Cancels all of the unfinished jobs
+/// * `Resolve` - Resolves the first conflict a job waits for a decision
+///   on with the given resolution
+/// * `ResolveAll` - Resolves all of the conflicts of the current jobs
+///   with the given resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Confirm,
+    EnterDir,
+    LeaveDir,
+    Next,
+    Previous,
+    FocusLeft,
+    FocusRight,
+    Mark(State),
+    Refresh,
+    ToggleJobs,
+    PauseJob,
+    CancelJob,
+    PauseAllJobs,
+    CancelAllJobs,
+    Resolve(Resolution),
+    ResolveAll(Resolution),
+}
+
+/// Hints describing the keys bound to the actions performed on the
+/// lists by the default keymap
+pub const KEY_HINTS: [(Action, &str); 8] = [
+    (Action::EnterDir, "SPACE open"),
+    (Action::LeaveDir, "BACKSPACE up"),
+    (Action::Mark(State::ToMove), "M move"),
+    (Action::Mark(State::ToCopy), "C copy"),
+    (Action::Mark(State::ToDelete), "D delete"),
+    (Action::Refresh, "R refresh"),
+    (Action::Confirm, "ENTER run"),
+    (Action::ToggleJobs, "T transfers"),
+];
+
+/// Hints describing the keys bound to the actions performed on the
+/// list of jobs by the default keymap
+pub const JOB_KEY_HINTS: [(Action, &str); 5] = [
+    (Action::PauseJob, "P pause/resume"),
+    (Action::CancelJob, "X cancel"),
+    (Action::PauseAllJobs, "SHIFT+P pause/resume all"),
+    (Action::CancelAllJobs, "SHIFT+X cancel all"),
+    (Action::ToggleJobs, "T back"),
+];
+
+/// Hints describing the keys bound to the resolutions of conflicts by
+/// the default keymap
+pub const CONFLICT_KEY_HINTS: [(Action, &str); 6] = [
+    (Action::Resolve(Resolution::Overwrite), "O overwrite"),
+    (Action::Resolve(Resolution::Skip), "S skip"),
+    (Action::Resolve(Resolution::Rename), "N keep both"),
+    (
+        Action::ResolveAll(Resolution::Overwrite),
+        "SHIFT+O overwrite all",
+    ),
+    (Action::ResolveAll(Resolution::Skip), "SHIFT+S skip all"),
+    (Action::ResolveAll(Resolution::Rename), "SHIFT+N keep all"),
+];
+
+impl Action {
+    /// Maps given key onto an action according to the default keymap,
+    /// returns `None` if the key isn't bound to any action
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    /// use versfm::{components::State, screens::Action};
+    /// let key = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+    /// assert_eq!(Action::from_key_event(&key), Some(Action::Mark(State::ToCopy)));
+    /// ```
+    pub fn from_key_event(key: &KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Enter => Some(Action::Confirm),
+            KeyCode::Char(' ') => Some(Action::EnterDir),
+            KeyCode::Backspace => Some(Action::LeaveDir),
+            KeyCode::Down | KeyCode::Char('j') => Some(Action::Next),
+            KeyCode::Up | KeyCode::Char('k') => Some(Action::Previous),
+            KeyCode::Left | KeyCode::Char('h') => Some(Action::FocusLeft),
+            KeyCode::Right | KeyCode::Char('l') => Some(Action::FocusRight),
+            KeyCode::Char('m') => Some(Action::Mark(State::ToMove)),
+            KeyCode::Char('c') => Some(Action::Mark(State::ToCopy)),
+            KeyCode::Char('d') => Some(Action::Mark(State::ToDelete)),
+            KeyCode::Char('r') => Some(Action::Refresh),
+            KeyCode::Char('t') => Some(Action::ToggleJobs),
+            KeyCode::Char('p') => Some(Action::PauseJob),
+            KeyCode::Char('x') => Some(Action::CancelJob),
+            KeyCode::Char('P') => Some(Action::PauseAllJobs),
+            KeyCode::Char('X') => Some(Action::CancelAllJobs),
+            KeyCode::Char('o') => Some(Action::Resolve(Resolution::Overwrite)),
+            KeyCode::Char('s') => Some(Action::Resolve(Resolution::Skip)),
+            KeyCode::Char('n') => Some(Action::Resolve(Resolution::Rename)),
+            KeyCode::Char('O') => Some(Action::ResolveAll(Resolution::Overwrite)),
+            KeyCode::Char('S') => Some(Action::ResolveAll(Resolution::Skip)),
+            KeyCode::Char('N') => Some(Action::ResolveAll(Resolution::Rename)),
+            _ => None,
+        }
+    }
+}
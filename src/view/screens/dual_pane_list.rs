@@ -1,22 +1,29 @@
-use crossterm::{
-    event::{DisableMouseCapture, KeyCode, KeyEvent},
-    execute,
-    terminal::{disable_raw_mode, LeaveAlternateScreen},
-};
 use std::{
+    collections::HashMap,
     error::Error,
-    io::Stdout,
+    ffi::OsString,
     sync::{Arc, Mutex, MutexGuard},
 };
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::error;
 use tui::{
-    backend::CrosstermBackend,
+    backend::Backend,
     layout::{Constraint, Direction, Layout},
-    widgets::{List, ListItem},
+    style::{Color, Style},
+    widgets::{List, ListItem, ListState, Paragraph},
     Terminal,
 };
 
+use super::{
+    progress::{make_conflict_prompt, make_jobs_list, make_progress_panel},
+    Action, Event, CONFLICT_KEY_HINTS, JOB_KEY_HINTS, KEY_HINTS,
+};
 use crate::{
-    utils::append_path_to_dir,
+    path::VirtualPath,
+    providers::{Kind, LinkPolicy},
+    transfer::{
+        Job, JobId, JobKind, JobState, Location, TransferEngine, TransferEvent, TransferOptions,
+    },
     view::components::{err::ComponentError, FileCRUDListWidget, State},
 };
 
@@ -39,28 +46,194 @@ fn get_err_list<'err_stack_lif>(
         .collect()
 }
 
+/// Logs given error and pushes it onto the error stack
+fn push_err(err_stack: &Mutex<Vec<ComponentError>>, e: ComponentError) {
+    error!(
+        component = e.component(),
+        code = e.code(),
+        "{}",
+        e.message()
+    );
+    err_stack
+        .lock()
+        .expect("Couldn't lock err_stack mutex")
+        .push(e);
+}
+
+/// Job submitted from the screen, along with the lists it affects
+struct PaneJob {
+    from: Arc<Box<dyn FileCRUDListWidget>>,
+    to: Arc<Box<dyn FileCRUDListWidget>>,
+    file_name: OsString,
+    kind: JobKind,
+    source: VirtualPath,
+    destination: Option<VirtualPath>,
+}
+
+type PaneJobs = Arc<Mutex<HashMap<JobId, PaneJob>>>;
+
+fn lock_pane_jobs(pane_jobs: &PaneJobs) -> MutexGuard<'_, HashMap<JobId, PaneJob>> {
+    pane_jobs.lock().expect("Couldn't lock pane_jobs mutex")
+}
+
+/// Updates the lists affected by the finished job, and pushes the error
+/// it has failed with onto the error stack
+///
+/// * `destination` - Path the file has been written to, which differs
+///   from the one it was submitted with if the file has been renamed
+async fn finish_pane_job(
+    job: PaneJob,
+    state: &JobState,
+    destination: Option<VirtualPath>,
+    err_stack: &Mutex<Vec<ComponentError>>,
+) {
+    job.from.stop_processing_item(&job.file_name);
+    if let JobState::Failed(e) = state {
+        push_err(
+            err_stack,
+            ComponentError::from_provider_error(
+                "Transfer",
+                e.clone(),
+                Some(&job.source.to_string()),
+            ),
+        );
+    }
+    if matches!(state, JobState::Skipped) {
+        return;
+    }
+    if job.kind != JobKind::Copy {
+        job.from.refresh_entry(&job.source).await;
+    }
+    if let Some(destination) = destination.or(job.destination) {
+        job.to.refresh_entry(&destination).await;
+    }
+}
+
+/// Spawns a task updating the lists as the jobs submitted from the
+/// screen finish, and notifying the screen about the progress of jobs
+fn spawn_job_listener(
+    engine: TransferEngine,
+    mut transfer_events: UnboundedReceiver<TransferEvent>,
+    pane_jobs: PaneJobs,
+    err_stack: Arc<Mutex<Vec<ComponentError>>>,
+    events: UnboundedSender<Event<Action>>,
+) {
+    tokio::spawn(async move {
+        while let Some(event) = transfer_events.recv().await {
+            match &event {
+                TransferEvent::StateChanged { id, state } if state.is_finished() => {
+                    let job = lock_pane_jobs(&pane_jobs).remove(id);
+                    if let Some(job) = job {
+                        let destination = engine.job(*id).and_then(|info| info.destination);
+                        finish_pane_job(job, state, destination, &err_stack).await;
+                    }
+                }
+                TransferEvent::StateChanged {
+                    id,
+                    state: JobState::Running,
+                } => {
+                    if let Some(job) = lock_pane_jobs(&pane_jobs).get(id) {
+                        job.from.start_processing_item(&job.file_name);
+                    }
+                }
+                TransferEvent::StateChanged {
+                    id,
+                    state: JobState::Queued,
+                } => {
+                    if let Some(job) = lock_pane_jobs(&pane_jobs).get(id) {
+                        job.from.queue_item(&job.file_name);
+                    }
+                }
+                _ => (),
+            }
+            if events.send(Event::StateChanged).is_err() {
+                break;
+            }
+        }
+    });
+}
+
 /// Enum representing which list is currently under focus
 enum CurrentList {
     LeftList,
     RightList,
 }
 
+/// Enum representing what the screen currently displays
+enum CurrentView {
+    Lists,
+    Jobs,
+}
+
 /// A view consisting of two lists of file entries that can be
 /// moved, copied, deleted between one another
-pub struct DualPaneList {
-    term: Terminal<CrosstermBackend<Stdout>>,
+///
+/// The screen draws onto a terminal with any `tui` backend and is driven
+/// with `Action`s, so it can be rendered into a `TestBackend` and fed
+/// scripted input. Files are transferred by a `TransferEngine` the
+/// screen submits jobs to, as the jobs progress the screen sends
+/// `Event::StateChanged` through the `events` channel.
+///
+/// # Examples
+///
+/// ```
+/// use tokio::sync::mpsc;
+/// use tui::{backend::TestBackend, Terminal};
+/// use versfm::{
+///     components::ProviderList,
+///     providers::filesystem::FilesystemProvider,
+///     screens::{Action, DualPaneList},
+///     transfer::TransferOptions,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let term = Terminal::new(TestBackend::new(80, 20)).unwrap();
+/// let (tx, _rx) = mpsc::unbounded_channel();
+/// let mut screen = DualPaneList::new(
+///     term,
+///     Box::new(ProviderList::new(FilesystemProvider::new())),
+///     Box::new(ProviderList::new(FilesystemProvider::new())),
+///     TransferOptions::default(),
+///     tx,
+/// )
+/// .await;
+/// screen.handle_event(Action::FocusRight).await;
+/// screen.render().unwrap();
+///
+/// let rendered: String = screen
+///     .terminal()
+///     .backend()
+///     .buffer()
+///     .content()
+///     .iter()
+///     .map(|cell| cell.symbol.as_str())
+///     .collect();
+/// assert!(rendered.contains("@local:"));
+/// assert!(rendered.contains("SPACE open"));
+/// # }
+/// ```
+pub struct DualPaneList<B: Backend> {
+    term: Terminal<B>,
     curr_list: CurrentList,
     left_pane: Arc<Box<dyn FileCRUDListWidget>>,
     right_pane: Arc<Box<dyn FileCRUDListWidget>>,
     err_stack: Arc<Mutex<Vec<ComponentError>>>,
+    engine: TransferEngine,
+    pane_jobs: PaneJobs,
+    status: Option<String>,
+    curr_view: CurrentView,
+    job_cursor: ListState,
 }
 
-impl DualPaneList {
+impl<B: Backend> DualPaneList<B> {
     pub async fn new(
-        term: Terminal<CrosstermBackend<Stdout>>,
+        term: Terminal<B>,
         left_pane: Box<dyn FileCRUDListWidget>,
         right_pane: Box<dyn FileCRUDListWidget>,
-    ) -> DualPaneList {
+        options: TransferOptions,
+        events: UnboundedSender<Event<Action>>,
+    ) -> DualPaneList<B> {
         let mut err_stack: Vec<ComponentError> = Vec::new();
         left_pane
             .refresh()
@@ -70,16 +243,31 @@ impl DualPaneList {
             .refresh()
             .await
             .unwrap_or_else(|e| err_stack.push(e));
+        let err_stack = Arc::new(Mutex::new(err_stack));
+        let pane_jobs = PaneJobs::default();
+        let (engine, transfer_events) = TransferEngine::new(options);
+        spawn_job_listener(
+            engine.clone(),
+            transfer_events,
+            pane_jobs.clone(),
+            err_stack.clone(),
+            events,
+        );
         DualPaneList {
             term,
             curr_list: CurrentList::LeftList,
             left_pane: Arc::new(left_pane),
             right_pane: Arc::new(right_pane),
-            err_stack: Arc::new(Mutex::new(err_stack)),
+            err_stack,
+            engine,
+            pane_jobs,
+            status: None,
+            curr_view: CurrentView::Lists,
+            job_cursor: ListState::default(),
         }
     }
 
-    fn lock_err_stack(&self) -> MutexGuard<Vec<ComponentError>> {
+    fn lock_err_stack(&self) -> MutexGuard<'_, Vec<ComponentError>> {
         self.err_stack
             .lock()
             .expect("Couldn't lock err_stack mutex")
@@ -87,7 +275,7 @@ impl DualPaneList {
 
     /// Handles given `ComponentError` by pushing it onto the error stack
     fn handle_err(&self, e: ComponentError) {
-        self.lock_err_stack().push(e);
+        push_err(&self.err_stack, e);
     }
 
     /// Return `true` if the error stack is empty
@@ -100,215 +288,322 @@ impl DualPaneList {
         self.lock_err_stack().clear()
     }
 
-    /// Handles the event sent to the applications by the input thread
-    pub async fn handle_event(&mut self, event: KeyEvent) {
+    /// Returns the reason why the given action can't be performed
+    /// on the focused list, or `None` if it can be performed
+    fn unsupported_reason(&self, action: Action) -> Option<String> {
         let curr_list = self.get_curr_list();
+        match action {
+            Action::EnterDir | Action::LeaveDir if !curr_list.get_capabilities().directories => {
+                Some(format!(
+                    "{} doesn't support directories",
+                    curr_list.get_provider_name()
+                ))
+            }
+            Action::Mark(State::ToCopy | State::ToMove)
+                if self.engine.options().link_policy == LinkPolicy::Recreate =>
+            {
+                let other_list = self.get_other_list();
+                let is_link = curr_list
+                    .get_name_of_selected()
+                    .and_then(|name| curr_list.get_item_by_filename(&name))
+                    .is_some_and(|i| matches!(i.kind(), Kind::Symlink { .. }));
+                if is_link && !other_list.get_capabilities().symlinks {
+                    Some(format!(
+                        "Links can't be recreated in {}",
+                        other_list.get_provider_name()
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
 
-        match event.code {
-            KeyCode::Enter => {
-                if self.err_stack_empty() {
-                    self.move_items();
-                    self.copy_items();
-                    self.delete_items();
+    /// Performs the given action
+    ///
+    /// Actions the focused list doesn't support aren't performed, the
+    /// reason is displayed in the status line instead.
+    pub async fn handle_event(&mut self, action: Action) {
+        self.status = self.unsupported_reason(action);
+        if self.status.is_some() {
+            return;
+        }
+        match action {
+            Action::ToggleJobs => {
+                self.curr_view = match self.curr_view {
+                    CurrentView::Lists => CurrentView::Jobs,
+                    CurrentView::Jobs => CurrentView::Lists,
+                };
+                return;
+            }
+            Action::PauseAllJobs => {
+                let summary = self.engine.summary();
+                if summary.queued + summary.running > 0 {
+                    self.engine.pause_all();
                 } else {
-                    self.err_stack_clear();
+                    self.engine.resume_all();
+                }
+                return;
+            }
+            Action::CancelAllJobs => {
+                self.engine.cancel_all();
+                return;
+            }
+            Action::Resolve(resolution) => {
+                if let Some(job) = self.engine.conflicts().first() {
+                    self.engine.resolve(job.id, resolution);
                 }
+                return;
+            }
+            Action::ResolveAll(resolution) => {
+                self.engine.resolve_all(resolution);
+                return;
+            }
+            _ => (),
+        }
+        match self.curr_view {
+            CurrentView::Lists => self.handle_list_event(action).await,
+            CurrentView::Jobs => self.handle_jobs_event(action),
+        }
+    }
+
+    /// Performs the given action on the list of jobs
+    fn handle_jobs_event(&mut self, action: Action) {
+        let jobs = self.engine.jobs();
+        match action {
+            Action::Confirm => self.err_stack_clear(),
+            Action::Next if !jobs.is_empty() => {
+                let i = match self.job_cursor.selected() {
+                    Some(i) if i + 1 < jobs.len() => i + 1,
+                    _ => 0,
+                };
+                self.job_cursor.select(Some(i));
             }
-            KeyCode::Char(' ') => {
-                curr_list.move_into_selected_dir();
-                if let Err(e) = curr_list.refresh().await {
-                    curr_list.move_out_of_selected_dir();
-                    self.handle_err(e);
+            Action::Previous if !jobs.is_empty() => {
+                let i = match self.job_cursor.selected() {
+                    Some(i) if i > 0 && i < jobs.len() => i - 1,
+                    _ => jobs.len() - 1,
+                };
+                self.job_cursor.select(Some(i));
+            }
+            Action::PauseJob | Action::CancelJob => {
+                let job = match self.job_cursor.selected().and_then(|i| jobs.get(i)) {
+                    Some(job) => job,
+                    None => return,
+                };
+                match (action, &job.state) {
+                    (Action::CancelJob, _) => self.engine.cancel(job.id),
+                    (_, JobState::Paused) => self.engine.resume(job.id),
+                    _ => self.engine.pause(job.id),
                 }
             }
-            KeyCode::Backspace => {
-                curr_list.move_out_of_selected_dir();
-                if let Err(e) = curr_list.refresh().await {
-                    curr_list.move_into_selected_dir();
-                    self.handle_err(e);
+            _ => (),
+        }
+    }
+
+    /// Performs the given action on the lists
+    async fn handle_list_event(&mut self, action: Action) {
+        let curr_list = self.get_curr_list();
+
+        match action {
+            Action::Confirm => {
+                if self.err_stack_empty() {
+                    self.submit_marked_items().await;
+                } else {
+                    self.err_stack_clear();
                 }
             }
-            KeyCode::Down | KeyCode::Char('j') => curr_list.next(),
-            KeyCode::Up | KeyCode::Char('k') => curr_list.previous(),
-            KeyCode::Left | KeyCode::Char('h') => self.curr_list = CurrentList::LeftList,
-            KeyCode::Right | KeyCode::Char('l') => self.curr_list = CurrentList::RightList,
-            KeyCode::Char('m') => curr_list.select(State::ToMove),
-            KeyCode::Char('c') => curr_list.select(State::ToCopy),
-            KeyCode::Char('d') => curr_list.select(State::ToDelete),
-            KeyCode::Char('r') => self.refresh_lists().await,
+            Action::EnterDir => curr_list
+                .move_into_selected_dir()
+                .await
+                .unwrap_or_else(|e| self.handle_err(e)),
+            Action::LeaveDir => curr_list
+                .move_out_of_selected_dir()
+                .await
+                .unwrap_or_else(|e| self.handle_err(e)),
+            Action::Next => curr_list.next(),
+            Action::Previous => curr_list.previous(),
+            Action::FocusLeft => self.curr_list = CurrentList::LeftList,
+            Action::FocusRight => self.curr_list = CurrentList::RightList,
+            Action::Mark(state) => curr_list.select(state),
+            Action::Refresh => self.refresh_lists().await,
             _ => (),
         }
     }
 
-    /// Refreshes both of the lists
+    /// Reloads both of the lists, bypassing cached listings
     async fn refresh_lists(&mut self) {
         self.left_pane
-            .refresh()
+            .reload()
             .await
             .unwrap_or_else(|e| self.handle_err(e));
         self.right_pane
-            .refresh()
+            .reload()
             .await
             .unwrap_or_else(|e| self.handle_err(e));
     }
 
-    /// Copies items between the lists
-    fn copy_items(&self) {
-        self.copy_from_to(self.right_pane.clone(), self.left_pane.clone());
-        self.copy_from_to(self.left_pane.clone(), self.right_pane.clone());
-    }
-
-    fn copy_from_to(
-        &self,
-        from: Arc<Box<dyn FileCRUDListWidget>>,
-        to: Arc<Box<dyn FileCRUDListWidget>>,
-    ) {
-        for selected in from.get_selected(State::ToCopy) {
-            self.spawn_copy_task(from.clone(), to.clone(), selected.to_owned());
+    /// Submits jobs for all of the marked items of both lists
+    async fn submit_marked_items(&mut self) {
+        let (left, right) = (self.left_pane.clone(), self.right_pane.clone());
+        for (from, to) in [(&right, &left), (&left, &right)] {
+            for state in [State::ToMove, State::ToCopy, State::ToDelete] {
+                self.submit_marked(from, to, state).await;
+            }
         }
     }
 
-    /// Spawns a copy task for given file
-    fn spawn_copy_task(
-        &self,
-        from: Arc<Box<dyn FileCRUDListWidget>>,
-        to: Arc<Box<dyn FileCRUDListWidget>>,
-        file_name: String,
+    /// Submits a job for every item of the `from` list marked with the
+    /// given state, files are copied and moved into the `to` list
+    ///
+    /// Files that would be copied or moved onto themselves (eg. when both
+    /// lists display the same directory) are unmarked instead.
+    async fn submit_marked(
+        &mut self,
+        from: &Arc<Box<dyn FileCRUDListWidget>>,
+        to: &Arc<Box<dyn FileCRUDListWidget>>,
+        state: State,
     ) {
-        let err_stack = self.err_stack.clone();
-
-        let from_path = append_path_to_dir(&from.get_current_path(), &file_name);
-        let to_path = append_path_to_dir(&to.get_current_path(), &file_name);
-        tokio::spawn(async move {
-            match from.get_file_stream(&from_path).await {
-                Err(e) => err_stack
-                    .lock()
-                    .expect("Couldn't lock err_stack mutex")
-                    .push(e),
-                Ok(file) => {
-                    from.start_processing_item(&file_name);
-                    to.put_file(&to_path, file).await.unwrap_or_else(|e| {
-                        err_stack
-                            .lock()
-                            .expect("Couldn't lock err_stack mutex")
-                            .push(e)
-                    });
-                    from.stop_processing_item(&file_name);
-                }
+        let mut jobs = Vec::new();
+        for file_name in from.get_selected(state) {
+            let source = Location::new(
+                from.get_provider(),
+                from.get_current_path().join(&file_name),
+            );
+            let destination =
+                Location::new(to.get_provider(), to.get_current_path().join(&file_name));
+            if state != State::ToDelete && source.is_same_file(&destination).await {
+                self.status = Some(format!(
+                    "{} can't be copied or moved onto itself",
+                    file_name.to_string_lossy()
+                ));
+                from.stop_processing_item(&file_name);
+                continue;
             }
-        });
-    }
-
-    /// Deletes selected items from both lists
-    fn delete_items(&self) {
-        self.delete_from(self.left_pane.clone());
-        self.delete_from(self.right_pane.clone());
-    }
+            let size = from
+                .get_item_by_filename(&file_name)
+                .and_then(|i| i.metadata().size);
+            let job = match state {
+                State::ToMove => Job::move_file(source, destination),
+                State::ToDelete => Job::delete_file(source),
+                _ => Job::copy_file(source, destination),
+            }
+            .with_size_hint(size);
+            jobs.push((file_name, job));
+        }
 
-    fn delete_from(&self, from: Arc<Box<dyn FileCRUDListWidget>>) {
-        for selected in from.get_selected(State::ToDelete) {
-            self.spawn_delete_task(from.clone(), selected.to_owned());
+        // Held until the jobs are recorded, so none of them can finish
+        // before the job listener knows about it
+        let mut pane_jobs = lock_pane_jobs(&self.pane_jobs);
+        for (file_name, job) in jobs {
+            let pane_job = PaneJob {
+                from: from.clone(),
+                to: to.clone(),
+                file_name: file_name.clone(),
+                kind: job.kind(),
+                source: job.source().path.clone(),
+                destination: job.destination().map(|d| d.path.clone()),
+            };
+            from.queue_item(&file_name);
+            pane_jobs.insert(self.engine.submit(job), pane_job);
         }
     }
 
-    /// Spawns a delete task for given file
-    fn spawn_delete_task(&self, from: Arc<Box<dyn FileCRUDListWidget>>, file_name: String) {
-        let err_stack = self.err_stack.clone();
-
-        let from_path = append_path_to_dir(&from.get_current_path(), &file_name);
-        tokio::spawn(async move {
-            from.start_processing_item(&file_name);
-            from.delete_file(&from_path).await.unwrap_or_else(|e| {
-                err_stack
-                    .lock()
-                    .expect("Couldn't lock err_stack mutex")
-                    .push(e)
-            });
-        });
+    /// Returns the list that is currently selected
+    fn get_curr_list(&self) -> Arc<Box<dyn FileCRUDListWidget>> {
+        match self.curr_list {
+            CurrentList::LeftList => self.left_pane.clone(),
+            CurrentList::RightList => self.right_pane.clone(),
+        }
     }
 
-    /// Moves items between lists
-    fn move_items(&self) {
-        self.move_from_to(self.right_pane.clone(), self.left_pane.clone());
-        self.move_from_to(self.left_pane.clone(), self.right_pane.clone());
+    /// Returns the list that is currently not selected
+    fn get_other_list(&self) -> Arc<Box<dyn FileCRUDListWidget>> {
+        match self.curr_list {
+            CurrentList::LeftList => self.right_pane.clone(),
+            CurrentList::RightList => self.left_pane.clone(),
+        }
     }
 
-    fn move_from_to(
-        &self,
-        from: Arc<Box<dyn FileCRUDListWidget>>,
-        to: Arc<Box<dyn FileCRUDListWidget>>,
-    ) {
-        for selected in from.get_selected(State::ToMove) {
-            let from = from.clone();
-            let to = to.clone();
-            self.spawn_move_task(from, to, selected.to_owned());
+    /// Creates the status line, displaying the status message if there
+    /// is one, or the keys available in the focused list otherwise
+    fn make_status_line(&self) -> Paragraph<'static> {
+        match &self.status {
+            Some(status) => {
+                Paragraph::new(status.clone()).style(Style::default().fg(Color::Yellow))
+            }
+            None => {
+                let key_hints: &[(Action, &str)] = match self.curr_view {
+                    _ if !self.engine.conflicts().is_empty() => &CONFLICT_KEY_HINTS,
+                    CurrentView::Lists => &KEY_HINTS,
+                    CurrentView::Jobs => &JOB_KEY_HINTS,
+                };
+                let hints: Vec<&str> = key_hints
+                    .iter()
+                    .filter(|(action, _)| self.unsupported_reason(*action).is_none())
+                    .map(|(_, hint)| *hint)
+                    .collect();
+                Paragraph::new(hints.join("  ")).style(Style::default().fg(Color::DarkGray))
+            }
         }
     }
 
-    /// Spawn move task for the given file
-    fn spawn_move_task(
-        &self,
-        from: Arc<Box<dyn FileCRUDListWidget>>,
-        to: Arc<Box<dyn FileCRUDListWidget>>,
-        file_name: String,
-    ) {
-        let err_stack = self.err_stack.clone();
-
-        let from_path = append_path_to_dir(&from.get_current_path(), &file_name);
-        let to_path = append_path_to_dir(&to.get_current_path(), &file_name);
-
-        tokio::spawn(async move {
-            match from.get_file_stream(&from_path).await {
-                Ok(file) => {
-                    from.start_processing_item(&file_name);
-                    to.put_file(&to_path, file).await.unwrap_or_else(|e| {
-                        err_stack
-                            .lock()
-                            .expect("Couldn't lock err_stack mutex")
-                            .push(e)
-                    });
-                    from.delete_file(&from_path).await.unwrap_or_else(|e| {
-                        err_stack
-                            .lock()
-                            .expect("Couldn't lock err_stack mutex")
-                            .push(e)
-                    });
-                }
-                Err(e) => err_stack.lock().unwrap().push(e),
-            }
-        });
+    /// Cancels the jobs that haven't finished yet, and waits until they
+    /// have stopped, so none of them is abandoned half-way
+    pub async fn shutdown(&self) {
+        self.engine.shutdown().await;
     }
 
-    /// Returns the list that is currently selected
-    fn get_curr_list(&mut self) -> Arc<Box<dyn FileCRUDListWidget>> {
-        match self.curr_list {
-            CurrentList::LeftList => self.left_pane.clone(),
-            CurrentList::RightList => self.right_pane.clone(),
-        }
+    /// Returns the terminal this screen draws onto
+    pub fn terminal(&self) -> &Terminal<B> {
+        &self.term
     }
 
-    /// Shuts down this screen, releases the terminal etc.
-    pub fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
-        disable_raw_mode()?;
-        execute!(
-            self.term.backend_mut(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        )?;
-        self.term.show_cursor()?;
-        self.term.clear()?;
-        Ok(())
+    /// Returns the terminal this screen draws onto, so it can be
+    /// restored once the screen is no longer used
+    pub fn terminal_mut(&mut self) -> &mut Terminal<B> {
+        &mut self.term
     }
 
     /// Renders this screen
     pub fn render(&mut self) -> Result<(), Box<dyn Error>> {
         let term_size = self.term.size().unwrap();
-        if self.err_stack_empty() {
+        if self.err_stack_empty() && matches!(self.curr_view, CurrentView::Jobs) {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([Constraint::Min(0), Constraint::Length(1)])
+                .split(term_size);
+            let jobs_list = make_jobs_list(&self.engine.jobs());
+            let status_line = self.make_status_line();
+
+            self.term.draw(|f| {
+                f.render_stateful_widget(jobs_list, rows[0], &mut self.job_cursor);
+                f.render_widget(status_line, rows[1]);
+            })?;
+        } else if self.err_stack_empty() {
+            let summary = self.engine.summary();
+            let progress_panel = (summary.unfinished() > 0)
+                .then(|| make_progress_panel(&self.engine.active_jobs(), &summary));
+            let panel_height = progress_panel.as_ref().map_or(0, |(_, height)| *height);
+            let conflict_prompt = make_conflict_prompt(&self.engine.conflicts());
+            let prompt_height = conflict_prompt.as_ref().map_or(0, |(_, height)| *height);
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(prompt_height),
+                    Constraint::Length(panel_height),
+                    Constraint::Length(1),
+                ])
+                .split(term_size);
             let chunks = Layout::default()
                 .direction(Direction::Horizontal)
-                .margin(1)
                 .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                .split(term_size);
+                .split(rows[0]);
+            let status_line = self.make_status_line();
 
             self.term.draw(|f| {
                 f.render_stateful_widget(
@@ -323,6 +618,13 @@ impl DualPaneList {
                     chunks[1],
                     &mut self.right_pane.get_current(),
                 );
+                if let Some((prompt, _)) = conflict_prompt {
+                    f.render_widget(prompt, rows[1]);
+                }
+                if let Some((panel, _)) = progress_panel {
+                    f.render_widget(panel, rows[2]);
+                }
+                f.render_widget(status_line, rows[3]);
             })?;
         } else {
             let chunks = Layout::default()
@@ -0,0 +1,216 @@
+//! Module creating the widgets displaying transfers and their progress
+use std::time::Duration;
+
+use tui::{
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem},
+};
+
+use crate::{
+    providers::Metadata,
+    transfer::{JobInfo, JobKind, JobState, JobSummary, Progress},
+};
+
+/// Maximum number of active jobs listed in the panel
+const MAX_LISTED_JOBS: usize = 5;
+
+/// Formats given number of bytes using binary units (eg. `1.5 MiB`)
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formats given duration as `m:ss`, or `h:mm:ss` if it exceeds an hour
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=3599 => format!("{}:{:02}", secs / 60, secs % 60),
+        _ => format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Describes given progress, skipping the parts that can't be computed
+///
+/// eg. `12.0 MiB / 48.0 MiB  25%  4.0 MiB/s  ETA 0:09`
+fn format_progress(progress: &Progress) -> String {
+    let mut text = format_bytes(progress.transferred);
+    if let Some(size) = progress.size {
+        text.push_str(&format!(" / {}", format_bytes(size)));
+    }
+    if let Some(percentage) = progress.percentage() {
+        text.push_str(&format!("  {:.0}%", percentage));
+    }
+    if let Some(throughput) = progress.throughput() {
+        text.push_str(&format!("  {}/s", format_bytes(throughput as u64)));
+    }
+    if let Some(eta) = progress.eta() {
+        text.push_str(&format!("  ETA {}", format_duration(eta)));
+    }
+    text
+}
+
+/// Describes what the job does and how far it has got
+fn format_job(job: &JobInfo) -> String {
+    let verb = match job.kind {
+        JobKind::Copy => "Copying",
+        JobKind::Move => "Moving",
+        JobKind::Delete => "Deleting",
+    };
+    let name = job.source.file_name().unwrap_or_default().to_string_lossy();
+    let mut text = match job.kind {
+        JobKind::Delete => format!("{} {}", verb, name),
+        _ => format!("{} {}  {}", verb, name, format_progress(&job.progress())),
+    };
+    if job.retries > 0 {
+        text.push_str(&format!("  (retried {}x)", job.retries));
+    }
+    text
+}
+
+/// Returns the label and the color the state of a job is displayed with
+fn describe_state(state: &JobState) -> (&'static str, Color) {
+    match state {
+        JobState::Queued => ("queued", Color::DarkGray),
+        JobState::Running => ("running", Color::LightBlue),
+        JobState::Paused => ("paused", Color::Yellow),
+        JobState::Completed => ("done", Color::Green),
+        JobState::Skipped => ("skipped", Color::Green),
+        JobState::Cancelled => ("cancelled", Color::DarkGray),
+        JobState::Failed(_) => ("failed", Color::Red),
+    }
+}
+
+/// Describes the size and the modification time of a file
+///
+/// eg. `1.5 MiB, modified 2022-03-14 09:26`
+fn format_metadata(metadata: &Metadata) -> String {
+    let size = metadata
+        .size
+        .map_or_else(|| String::from("unknown size"), format_bytes);
+    match metadata.modified {
+        Some(modified) => format!("{}, modified {}", size, modified.format("%Y-%m-%d %H:%M")),
+        None => size,
+    }
+}
+
+/// Creates the list of all of the given jobs, describing their state
+/// and progress
+pub(super) fn make_jobs_list(jobs: &[JobInfo]) -> List<'static> {
+    let items: Vec<ListItem> = jobs
+        .iter()
+        .map(|job| {
+            let (label, color) = match job.conflict {
+                Some(_) => ("conflict", Color::Yellow),
+                None => describe_state(&job.state),
+            };
+            let mut text = format!("{} [{}] {}", job.id, label, format_job(job));
+            if let JobState::Failed(e) = &job.state {
+                text.push_str(&format!("  {}", e.message()));
+            }
+            ListItem::new(text).style(Style::default().fg(color))
+        })
+        .collect();
+    List::new(items)
+        .block(
+            Block::default()
+                .title("Transfers")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::LightBlue)),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ")
+}
+
+/// Creates the panel listing the active jobs and the progress of the
+/// whole batch, along with the height it should be rendered with
+pub(super) fn make_progress_panel(
+    active: &[JobInfo],
+    summary: &JobSummary,
+) -> (List<'static>, u16) {
+    let mut items: Vec<ListItem> = active
+        .iter()
+        .take(MAX_LISTED_JOBS)
+        .map(|job| match job.state {
+            JobState::Paused => ListItem::new(format!("(paused) {}", format_job(job)))
+                .style(Style::default().fg(Color::Yellow)),
+            _ => ListItem::new(format_job(job)),
+        })
+        .collect();
+    if active.len() > MAX_LISTED_JOBS {
+        items.push(ListItem::new(format!(
+            "... and {} more",
+            active.len() - MAX_LISTED_JOBS
+        )));
+    }
+    items.push(
+        ListItem::new(format!(
+            "Total: {} of {} files ({} running, {} queued, {} paused, {} failed)  {}",
+            summary.total() - summary.unfinished(),
+            summary.total(),
+            summary.running,
+            summary.queued,
+            summary.paused,
+            summary.failed,
+            format_progress(&summary.progress)
+        ))
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    );
+    // Borders take up two more rows
+    let height = items.len() as u16 + 2;
+    let panel = List::new(items).block(
+        Block::default()
+            .title("Transfers")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::LightBlue)),
+    );
+    (panel, height)
+}
+
+/// Creates the prompt asking what to do with the file already present in
+/// the destination of the first of the given jobs waiting for a decision,
+/// along with the height it should be rendered with
+///
+/// Returns `None` if none of the jobs waits for a decision.
+pub(super) fn make_conflict_prompt(jobs: &[JobInfo]) -> Option<(List<'static>, u16)> {
+    let pending = jobs.iter().filter(|job| job.conflict.is_some()).count();
+    let (job, conflict) = jobs
+        .iter()
+        .find_map(|job| Some((job, job.conflict.as_ref()?)))?;
+    let destination = job
+        .destination
+        .as_ref()
+        .map(|d| d.to_string())
+        .unwrap_or_default();
+    let mut title = String::from("File already exists");
+    if pending > 1 {
+        title.push_str(&format!(" (1 of {})", pending));
+    }
+    let items = vec![
+        ListItem::new(destination).style(Style::default().add_modifier(Modifier::BOLD)),
+        ListItem::new(format!(
+            "Existing: {}",
+            format_metadata(&conflict.destination)
+        )),
+        ListItem::new(format!("New:      {}", format_metadata(&conflict.source))),
+    ];
+    let height = items.len() as u16 + 2;
+    let prompt = List::new(items)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .style(Style::default().fg(Color::White));
+    Some((prompt, height))
+}
@@ -0,0 +1,134 @@
+//! Tests of the cache of directory listings
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+use versfm::{
+    path::VirtualPath,
+    providers::{
+        cache::{CachedProvider, ListingCache},
+        err::ProviderError,
+        BoxedByteStream, Capabilities, Kind, Metadata, Object, Provider,
+    },
+};
+
+/// Maximum time a test waits for a listing to start
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Files of the mock directory, along with the gate its listings wait for
+struct Directory {
+    names: Mutex<Vec<String>>,
+    gate: Semaphore,
+    /// Number of listings that have started
+    lists: AtomicUsize,
+}
+
+/// Provider of a single flat directory, whose listings are taken as soon
+/// as they start, but only returned once a permit of the gate is given
+struct GatedProvider(Arc<Directory>);
+
+#[async_trait]
+impl Provider for GatedProvider {
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
+
+    fn resource_name(&self) -> &str {
+        "mock"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        VirtualPath::new("mock", "mock")
+    }
+
+    async fn list(&self, _path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        let names = self.0.names.lock().unwrap().clone();
+        self.0.lists.fetch_add(1, Ordering::SeqCst);
+        self.0.gate.acquire().await.unwrap().forget();
+        Ok(names
+            .into_iter()
+            .map(|name| Object {
+                name: name.into(),
+                kind: Kind::File,
+                metadata: Metadata::default(),
+            })
+            .collect())
+    }
+
+    async fn stat(&self, _path: &VirtualPath) -> Result<Object, ProviderError> {
+        Err(ProviderError::unsupported("stat"))
+    }
+
+    async fn read(&self, _path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        Err(ProviderError::unsupported("read"))
+    }
+
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        _stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        self.0.names.lock().unwrap().push(name);
+        Ok(())
+    }
+
+    async fn delete(&self, _path: &VirtualPath) -> Result<(), ProviderError> {
+        Err(ProviderError::unsupported("delete"))
+    }
+}
+
+fn names(objects: &[Object]) -> Vec<String> {
+    objects
+        .iter()
+        .map(|o| o.name.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn doesnt_cache_listings_fetched_before_a_write_finished() {
+    let directory = Arc::new(Directory {
+        names: Mutex::new(vec![String::from("a.txt")]),
+        gate: Semaphore::new(0),
+        lists: AtomicUsize::new(0),
+    });
+    let provider = Arc::new(CachedProvider::new(
+        GatedProvider(directory.clone()),
+        ListingCache::new(Duration::from_secs(60)),
+    ));
+    let dir = provider.initial_path();
+
+    let listing = tokio::spawn({
+        let (provider, dir) = (provider.clone(), dir.clone());
+        async move { provider.list(&dir).await }
+    });
+    tokio::time::timeout(TIMEOUT, async {
+        while directory.lists.load(Ordering::SeqCst) == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Listing hasn't started in time");
+    provider
+        .write(&dir.join("b.txt"), Box::pin(futures::stream::empty()))
+        .await
+        .unwrap();
+    directory.gate.add_permits(2);
+
+    let stale = listing.await.unwrap().unwrap();
+    assert_eq!(names(&stale), ["a.txt"]);
+    let listed = provider.list(&dir).await.unwrap();
+    assert_eq!(names(&listed), ["a.txt", "b.txt"]);
+    assert_eq!(directory.lists.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,32 @@
+//! Tests of the checksums transferred files are verified with
+use versfm::transfer::Checksums;
+
+/// Returns the multipart ETag of given data, uploaded in parts of 4 bytes
+fn multipart_etag(data: &[u8]) -> String {
+    Checksums::of(data, Some(4)).multipart_etag.unwrap()
+}
+
+#[test]
+fn computes_multipart_etags_of_files_smaller_than_a_part() {
+    assert_eq!(multipart_etag(b"abc"), "af5da9f45af7a300e3aded972f8ff687-1");
+}
+
+#[test]
+fn computes_multipart_etags_of_files_of_whole_parts() {
+    assert_eq!(
+        multipart_etag(b"abcd"),
+        "1243e2c5302cae4b559ce80dd1cefa6e-1"
+    );
+    assert_eq!(
+        multipart_etag(b"abcdefgh"),
+        "cb93ad6c9c920e2602b79a11ded63ddb-2"
+    );
+}
+
+#[test]
+fn computes_multipart_etags_of_files_ending_with_a_partial_part() {
+    assert_eq!(
+        multipart_etag(b"abcdefghij"),
+        "446feba4c1b5cc7ad93bf4d44a0e36ac-3"
+    );
+}
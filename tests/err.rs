@@ -0,0 +1,38 @@
+//! Tests of the errors providers return
+use std::io;
+
+use versfm::providers::err::{ErrorKind, ProviderError};
+
+#[test]
+fn maps_io_errors_onto_their_kinds() {
+    let kinds = [
+        (io::ErrorKind::NotFound, ErrorKind::NotFound),
+        (io::ErrorKind::PermissionDenied, ErrorKind::PermissionDenied),
+        (io::ErrorKind::AlreadyExists, ErrorKind::Conflict),
+        (io::ErrorKind::InvalidData, ErrorKind::InvalidData),
+        (io::ErrorKind::InvalidInput, ErrorKind::InvalidInput),
+        (io::ErrorKind::WriteZero, ErrorKind::InvalidData),
+        (io::ErrorKind::UnexpectedEof, ErrorKind::InvalidData),
+        (io::ErrorKind::Interrupted, ErrorKind::Transient),
+        (io::ErrorKind::TimedOut, ErrorKind::Transient),
+        (io::ErrorKind::WouldBlock, ErrorKind::Transient),
+        (io::ErrorKind::ConnectionReset, ErrorKind::Transient),
+        (io::ErrorKind::ConnectionAborted, ErrorKind::Transient),
+        (io::ErrorKind::BrokenPipe, ErrorKind::Transient),
+        (io::ErrorKind::Unsupported, ErrorKind::Unsupported),
+        (io::ErrorKind::OutOfMemory, ErrorKind::Other),
+        (io::ErrorKind::Other, ErrorKind::Other),
+    ];
+    for (io_kind, kind) in kinds {
+        let err = ProviderError::from(io::Error::new(io_kind, "failed"));
+
+        assert_eq!(err.kind(), kind, "{:?}", io_kind);
+        assert_eq!(err.code(), format!("{:?}", io_kind));
+        assert_eq!(
+            err.is_retryable(),
+            kind == ErrorKind::Transient,
+            "{:?}",
+            io_kind
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Tests of the filesystem provider
+use std::{io, path::Path, pin::Pin, time::Duration};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use versfm::{
+    path::VirtualPath,
+    providers::{err::ErrorKind, filesystem::FilesystemProvider, BoxedByteStream, Provider},
+};
+
+/// Creates an empty directory for the test of given name
+async fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("versfm-{}-{}", name, std::process::id()));
+    tokio::fs::remove_dir_all(&dir).await.ok();
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    dir
+}
+
+fn virtual_path(path: &Path) -> VirtualPath {
+    format!("file://{}", path.display()).parse().unwrap()
+}
+
+/// Returns the names of the files in given directory
+async fn file_names(dir: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+    while let Some(entry) = entries.next_entry().await.unwrap() {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn failed_writes_leave_the_destination_untouched() {
+    let dir = test_dir("failed-write").await;
+    tokio::fs::write(dir.join("notes.txt"), "notes")
+        .await
+        .unwrap();
+    let chunks: Vec<Result<Bytes, io::Error>> = vec![
+        Ok(Bytes::from("new ")),
+        Err(io::Error::other("Source has failed")),
+    ];
+    let stream: Pin<BoxedByteStream> = Box::pin(futures::stream::iter(chunks));
+
+    let provider = FilesystemProvider::new();
+    let result = provider
+        .write(&virtual_path(&dir.join("notes.txt")), stream)
+        .await;
+    let names = file_names(&dir).await;
+    let contents = tokio::fs::read_to_string(dir.join("notes.txt")).await;
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    assert!(result.is_err());
+    assert_eq!(names, ["notes.txt"]);
+    assert_eq!(contents.unwrap(), "notes");
+}
+
+#[tokio::test]
+async fn abandoned_writes_remove_their_temporary_files() {
+    let dir = test_dir("abandoned-write").await;
+    let stream: Pin<BoxedByteStream> = Box::pin(
+        futures::stream::once(async { Ok(Bytes::from("partial")) })
+            .chain(futures::stream::pending()),
+    );
+
+    let provider = FilesystemProvider::new();
+    let path = virtual_path(&dir.join("notes.txt"));
+    let write = provider.write(&path, stream);
+    let result = tokio::time::timeout(Duration::from_millis(100), write).await;
+    assert!(result.is_err());
+    let mut names = file_names(&dir).await;
+    for _ in 0..100 {
+        if names.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        names = file_names(&dir).await;
+    }
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    assert!(names.is_empty(), "{:?} have been left behind", names);
+}
+
+#[tokio::test]
+async fn writes_replace_the_destination() {
+    let dir = test_dir("write").await;
+    tokio::fs::write(dir.join("notes.txt"), "old notes")
+        .await
+        .unwrap();
+    let stream: Pin<BoxedByteStream> =
+        Box::pin(futures::stream::once(async { Ok(Bytes::from("notes")) }));
+
+    let provider = FilesystemProvider::new();
+    provider
+        .write(&virtual_path(&dir.join("notes.txt")), stream)
+        .await
+        .unwrap();
+    let names = file_names(&dir).await;
+    let contents = tokio::fs::read_to_string(dir.join("notes.txt")).await;
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    assert_eq!(names, ["notes.txt"]);
+    assert_eq!(contents.unwrap(), "notes");
+}
+
+#[tokio::test]
+async fn refuses_names_escaping_their_directory() {
+    let dir = test_dir("escaping-names").await;
+    tokio::fs::create_dir(dir.join("inner")).await.unwrap();
+    let inner = virtual_path(&dir.join("inner"));
+
+    let provider = FilesystemProvider::new();
+    let mut results = Vec::new();
+    for name in ["..", ".", "", "/etc", "a/b"] {
+        let stream: Pin<BoxedByteStream> =
+            Box::pin(futures::stream::iter([Ok(Bytes::from("escaped"))]));
+        results.push(provider.write(&inner.join(name), stream).await);
+    }
+    let names = file_names(&dir).await;
+    let inner_names = file_names(&dir.join("inner")).await;
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    for result in results {
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    assert_eq!(names, ["inner"]);
+    assert!(inner_names.is_empty());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn lists_and_copies_files_whose_names_arent_utf8() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt, sync::Arc};
+    use versfm::transfer::{Job, JobState, Location, TransferEngine, TransferOptions};
+
+    let dir = test_dir("non-utf8").await;
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    tokio::fs::create_dir(dir.join("copies")).await.unwrap();
+    tokio::fs::write(dir.join(name), "latin-1").await.unwrap();
+
+    let provider = Arc::new(FilesystemProvider::new());
+    let listed = provider.list(&virtual_path(&dir)).await.unwrap();
+    let listed = listed.into_iter().find(|o| o.name == name).unwrap();
+    let (engine, _events) = TransferEngine::new(TransferOptions::default());
+    let location = |dir: &Path| {
+        let path = virtual_path(dir).join(&listed.name);
+        Location::new(provider.clone(), path)
+    };
+    let id = engine.submit(Job::copy_file(
+        location(&dir),
+        location(&dir.join("copies")),
+    ));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !engine.job(id).unwrap().state.is_finished() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Copy hasn't finished in time");
+    let state = engine.job(id).unwrap().state;
+    let copied = tokio::fs::read_to_string(dir.join("copies").join(name)).await;
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    assert!(matches!(state, JobState::Completed), "{:?}", state);
+    assert_eq!(copied.unwrap(), "latin-1");
+}
@@ -0,0 +1,198 @@
+//! Tests of how the plugin provider talks to plugins
+use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf, pin::Pin};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use versfm::{
+    path::VirtualPath,
+    providers::{err::ErrorKind, plugin::PluginProvider, BoxedByteStream, Kind, Provider},
+};
+
+/// Fake plugin keeping its files in memory, it starts with `notes.txt`,
+/// lists an entry named `../escaped` in the `evil` directory and exits
+/// when `crash` is stat'ed
+const PLUGIN: &str = r#"
+import base64, json, sys
+
+files = {"notes.txt": b"hello world"}
+
+def entry(name):
+    return {"name": name, "kind": "file", "size": len(files[name]), "etag": "e"}
+
+def handle(method, params):
+    path = "/".join(params.get("path", []))
+    if method == "initialize":
+        return {"provider_name": "fake", "resource_name": "memory"}
+    if method == "list" and path == "evil":
+        return [{"name": "../escaped", "kind": "file"}]
+    if method == "list":
+        return [entry(name) for name in sorted(files)]
+    if path == "crash":
+        sys.exit(1)
+    if path not in files and not (method == "write" and not params["append"]):
+        raise LookupError(path)
+    if method == "stat":
+        return entry(path)
+    if method == "read":
+        offset, length = params["offset"], params["length"]
+        return {"data": base64.b64encode(files[path][offset:offset + length]).decode()}
+    if method == "write":
+        data = base64.b64decode(params["data"])
+        files[path] = files[path] + data if params["append"] else data
+        return None
+    if method == "delete":
+        del files[path]
+        return None
+    return "unknown"
+
+for line in sys.stdin:
+    request = json.loads(line)
+    response = {"jsonrpc": "2.0", "id": request["id"]}
+    try:
+        result = handle(request["method"], request["params"])
+        if result == "unknown":
+            response["error"] = {"code": -32601, "message": "Method not found"}
+        else:
+            response["result"] = result
+    except LookupError as e:
+        response["error"] = {
+            "code": 1,
+            "message": "No such file: " + str(e),
+            "data": {"kind": "NotFound", "code": "NoSuchFile"},
+        }
+    print(json.dumps(response), flush=True)
+"#;
+
+/// Writes the fake plugin to a file for the test of given name
+async fn plugin_script(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("versfm-plugin-{}-{}.py", name, std::process::id()));
+    tokio::fs::write(&path, PLUGIN).await.unwrap();
+    path
+}
+
+/// Spawns the fake plugin for the test of given name
+async fn plugin(name: &str) -> PluginProvider {
+    let script = plugin_script(name).await;
+    let provider = PluginProvider::new("python3", &[script.to_str().unwrap()])
+        .await
+        .unwrap();
+    tokio::fs::remove_file(&script).await.unwrap();
+    provider
+}
+
+fn path(provider: &PluginProvider, name: &str) -> VirtualPath {
+    provider.initial_path().join(name)
+}
+
+/// Reads the whole stream into a byte vector
+async fn collect(mut stream: Pin<BoxedByteStream>) -> Vec<u8> {
+    let mut data = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        data.extend_from_slice(&chunk.unwrap());
+    }
+    data
+}
+
+fn stream_of(chunks: &[&'static str]) -> Pin<BoxedByteStream> {
+    let chunks: Vec<_> = chunks.iter().map(|c| Ok(Bytes::from(*c))).collect();
+    Box::pin(futures::stream::iter(chunks))
+}
+
+#[tokio::test]
+async fn lists_and_stats_files() {
+    let provider = plugin("list").await;
+
+    let listed = provider.list(&provider.initial_path()).await.unwrap();
+    let stat = provider.stat(&path(&provider, "notes.txt")).await.unwrap();
+
+    assert_eq!(provider.provider_name(), "fake");
+    assert_eq!(provider.resource_name(), "memory");
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].name, "notes.txt");
+    assert_eq!(stat.name, "notes.txt");
+    assert!(matches!(stat.kind, Kind::File));
+    assert_eq!(stat.metadata.size, Some(11));
+    assert_eq!(stat.metadata.etag.as_deref(), Some("e"));
+}
+
+#[tokio::test]
+async fn reads_and_writes_files() {
+    let provider = plugin("read-write").await;
+    let notes = path(&provider, "notes.txt");
+    let copy = path(&provider, "copy.txt");
+
+    let whole = collect(provider.read(&notes).await.unwrap()).await;
+    let range = collect(provider.read_range(&notes, 6, Some(3)).await.unwrap()).await;
+    provider
+        .write(&copy, stream_of(&["first ", "second"]))
+        .await
+        .unwrap();
+    let written = collect(provider.read(&copy).await.unwrap()).await;
+    provider.delete(&copy).await.unwrap();
+    let deleted = provider.stat(&copy).await;
+
+    assert_eq!(whole, b"hello world");
+    assert_eq!(range, b"wor");
+    assert_eq!(written, b"first second");
+    assert_eq!(deleted.err().unwrap().kind(), ErrorKind::NotFound);
+}
+
+#[tokio::test]
+async fn maps_error_replies() {
+    let provider = plugin("errors").await;
+
+    let missing = provider
+        .stat(&path(&provider, "missing.txt"))
+        .await
+        .err()
+        .unwrap();
+    let renamed = provider
+        .rename(&path(&provider, "notes.txt"), &path(&provider, "moved.txt"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(missing.kind(), ErrorKind::NotFound);
+    assert_eq!(missing.code(), "NoSuchFile");
+    assert_eq!(missing.message(), "No such file: missing.txt");
+    assert_eq!(renamed.kind(), ErrorKind::Unsupported);
+}
+
+#[tokio::test]
+async fn fails_requests_once_the_plugin_has_crashed() {
+    let provider = plugin("crash").await;
+
+    let crashed = provider
+        .stat(&path(&provider, "crash"))
+        .await
+        .err()
+        .unwrap();
+    let after = provider.list(&provider.initial_path()).await.err().unwrap();
+
+    assert_eq!(crashed.kind(), ErrorKind::Other);
+    assert_eq!(after.kind(), ErrorKind::Other);
+}
+
+#[tokio::test]
+async fn rejects_malformed_entry_names() {
+    let provider = plugin("malformed").await;
+
+    let listed = provider.list(&path(&provider, "evil")).await.err().unwrap();
+
+    assert_eq!(listed.kind(), ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn refuses_names_that_arent_utf8() {
+    let provider = plugin("non-utf8").await;
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+
+    let written = provider
+        .write(&provider.initial_path().join(name), stream_of(&["latin-1"]))
+        .await
+        .unwrap_err();
+    let listed = provider.list(&provider.initial_path()).await.unwrap();
+
+    assert_eq!(written.kind(), ErrorKind::InvalidInput);
+    assert_eq!(listed.len(), 1);
+}
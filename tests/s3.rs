@@ -0,0 +1,189 @@
+//! Tests of how the S3 provider talks to S3
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::FutureExt;
+use rusoto_core::{
+    credential::StaticProvider,
+    request::{DispatchSignedRequestFuture, HttpResponse},
+    signature::SignedRequest,
+    ByteStream, DispatchSignedRequest, Region,
+};
+use rusoto_s3::S3Client;
+use versfm::providers::{
+    err::ErrorKind,
+    s3::{S3Provider, MIN_PART_SIZE},
+    BoxedByteStream, Provider,
+};
+
+/// Maximum time a test waits for S3 to receive an expected request
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fake S3 recording the requests it receives, as `METHOD query-params`,
+/// along with the paths they were sent to
+#[derive(Clone, Default)]
+struct FakeS3 {
+    requests: Arc<Mutex<Vec<String>>>,
+    paths: Arc<Mutex<Vec<String>>>,
+    /// Status code and body every request is answered with, if set
+    failure: Option<(u16, &'static str)>,
+}
+
+impl FakeS3 {
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Waits until a request starting with given prefix has been received
+    async fn wait_for(&self, prefix: &str) {
+        tokio::time::timeout(TIMEOUT, async {
+            while !self.requests().iter().any(|r| r.starts_with(prefix)) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{} hasn't been received, got {:?}", prefix, self.requests()));
+    }
+}
+
+impl DispatchSignedRequest for FakeS3 {
+    fn dispatch(
+        &self,
+        request: SignedRequest,
+        _timeout: Option<Duration>,
+    ) -> DispatchSignedRequestFuture {
+        let params: Vec<&str> = request.params.keys().map(String::as_str).collect();
+        self.requests
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", request.method, params.join("&")));
+        self.paths.lock().unwrap().push(request.path.clone());
+        let body = match (request.method.as_str(), params.as_slice()) {
+            ("POST", ["uploads"]) => {
+                "<InitiateMultipartUploadResult><UploadId>upload</UploadId></InitiateMultipartUploadResult>"
+            }
+            _ => "",
+        };
+        let (status, body) = match self.failure {
+            Some((status, body)) => (status.try_into().unwrap(), body),
+            None => (Default::default(), body),
+        };
+        let response = HttpResponse {
+            status,
+            body: ByteStream::from(body.as_bytes().to_vec()),
+            headers: Default::default(),
+        };
+        async move { Ok(response) }.boxed()
+    }
+}
+
+fn provider(s3: &FakeS3) -> S3Provider {
+    let credentials = StaticProvider::new_minimal(String::from("key"), String::from("secret"));
+    let region = Region::Custom {
+        name: String::from("fake"),
+        endpoint: String::from("http://localhost"),
+    };
+    let s3_client = S3Client::new_with(s3.clone(), credentials, region);
+    S3Provider::with_client("bucket", s3_client).with_part_size(MIN_PART_SIZE)
+}
+
+#[tokio::test]
+async fn aborts_abandoned_multipart_uploads() {
+    let s3 = FakeS3::default();
+    let provider = provider(&s3);
+    // A stream of unknown length, stalling after its first part
+    let part = Bytes::from(vec![0; MIN_PART_SIZE]);
+    let stream: Pin<BoxedByteStream> =
+        Box::pin(futures::stream::unfold(Some(part), |part| async move {
+            match part {
+                Some(part) => Some((Ok(part), None)),
+                None => futures::future::pending().await,
+            }
+        }));
+    let path = provider.initial_path().join("large.bin");
+
+    let write = tokio::spawn(async move { provider.write(&path, stream).await });
+    s3.wait_for("PUT partNumber").await;
+    write.abort();
+    s3.wait_for("DELETE uploadId").await;
+
+    assert!(write.await.unwrap_err().is_cancelled());
+    assert_eq!(
+        s3.requests(),
+        ["POST uploads", "PUT partNumber&uploadId", "DELETE uploadId"]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn percent_encodes_bytes_of_keys_that_arent_utf8() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let s3 = FakeS3::default();
+    let provider = provider(&s3);
+    let dir = provider.initial_path().join("café");
+    for name in [
+        OsStr::new("notes 100%.txt"),
+        OsStr::from_bytes(b"caf\xe9.txt"),
+    ] {
+        provider.delete(&dir.join(name)).await.unwrap();
+    }
+
+    assert_eq!(
+        *s3.paths.lock().unwrap(),
+        ["/bucket/café/notes 100%.txt", "/bucket/café/caf%E9.txt"]
+    );
+}
+
+#[tokio::test]
+async fn maps_error_responses_with_and_without_xml_bodies() {
+    let responses = [
+        (404, "", ErrorKind::NotFound, "Not Found"),
+        (
+            503,
+            "<html>Service Unavailable</html>",
+            ErrorKind::Transient,
+            "Service Unavailable",
+        ),
+        (403, "Forbidden", ErrorKind::PermissionDenied, "Forbidden"),
+        (
+            409,
+            "<Error><Code>OperationAborted</Code><Message>Conflicting operation</Message></Error>",
+            ErrorKind::Conflict,
+            "OperationAborted",
+        ),
+    ];
+    for (status, body, kind, code) in responses {
+        let s3 = FakeS3 {
+            failure: Some((status, body)),
+            ..Default::default()
+        };
+        let provider = provider(&s3);
+        let path = provider.initial_path().join("notes.txt");
+
+        let deleted = provider.delete(&path).await.unwrap_err();
+        let stat = provider.stat(&path).await.err().unwrap();
+
+        for err in [deleted, stat] {
+            assert_eq!(err.kind(), kind, "{}", err);
+            assert_eq!(err.code(), code, "{}", err);
+            assert_eq!(err.is_retryable(), kind == ErrorKind::Transient);
+        }
+    }
+}
+
+#[tokio::test]
+async fn refuses_ranges_past_the_largest_offset() {
+    let s3 = FakeS3::default();
+    let provider = provider(&s3);
+    let path = provider.initial_path().join("notes.txt");
+
+    let read = provider.read_range(&path, u64::MAX - 1, Some(3)).await;
+
+    assert_eq!(read.err().unwrap().kind(), ErrorKind::InvalidInput);
+    assert!(s3.requests().is_empty());
+}
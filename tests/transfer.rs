@@ -0,0 +1,849 @@
+//! Tests of how the transfer engine runs jobs
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::sync::{mpsc::UnboundedReceiver, Semaphore};
+use versfm::{
+    path::VirtualPath,
+    providers::{
+        err::{ErrorKind, ProviderError},
+        filesystem::FilesystemProvider,
+        retry::RetryPolicy,
+        BoxedByteStream, Capabilities, Kind, LinkPolicy, Metadata, Object, Provider,
+    },
+    transfer::{
+        ConflictPolicy, Job, JobId, JobInfo, JobState, Location, TransferEngine, TransferEvent,
+        TransferOptions,
+    },
+};
+
+/// Maximum time a test waits for the engine to reach an expected state
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Deletions performed by mock providers, shared between all of them
+#[derive(Default)]
+struct Recorder {
+    /// Paths of the deleted files, in the order the deletions started in
+    started: Vec<VirtualPath>,
+    /// Number of running deletions, in total and per resource
+    running: usize,
+    running_per_resource: HashMap<String, usize>,
+    /// Highest number of deletions running at the same time, in total
+    /// and per resource
+    peak: usize,
+    peak_per_resource: HashMap<String, usize>,
+}
+
+/// Provider whose deletions wait for a permit of the gate shared by all
+/// of the mock providers, so they can be held running
+struct GatedProvider {
+    resource: String,
+    gate: Arc<Semaphore>,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+#[async_trait]
+impl Provider for GatedProvider {
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
+
+    fn resource_name(&self) -> &str {
+        &self.resource
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        VirtualPath::new("mock", &self.resource)
+    }
+
+    async fn list(&self, _path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        Err(ProviderError::unsupported("list"))
+    }
+
+    async fn stat(&self, _path: &VirtualPath) -> Result<Object, ProviderError> {
+        Err(ProviderError::unsupported("stat"))
+    }
+
+    async fn read(&self, _path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        Err(ProviderError::unsupported("read"))
+    }
+
+    async fn write(
+        &self,
+        _path: &VirtualPath,
+        _stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        Err(ProviderError::unsupported("write"))
+    }
+
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        {
+            let mut recorder = self.recorder.lock().unwrap();
+            recorder.started.push(path.clone());
+            recorder.running += 1;
+            recorder.peak = recorder.peak.max(recorder.running);
+            let running = recorder
+                .running_per_resource
+                .entry(self.resource.clone())
+                .or_default();
+            *running += 1;
+            let running = *running;
+            let peak = recorder
+                .peak_per_resource
+                .entry(self.resource.clone())
+                .or_default();
+            *peak = (*peak).max(running);
+        }
+        self.gate.acquire().await.unwrap().forget();
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.running -= 1;
+        *recorder
+            .running_per_resource
+            .get_mut(&self.resource)
+            .unwrap() -= 1;
+        Ok(())
+    }
+}
+
+/// Closed gate, along with providers of the given resources sharing it
+struct Setup {
+    gate: Arc<Semaphore>,
+    recorder: Arc<Mutex<Recorder>>,
+    providers: Vec<Arc<dyn Provider>>,
+}
+
+impl Setup {
+    fn new(resources: &[&str]) -> Setup {
+        let gate = Arc::new(Semaphore::new(0));
+        let recorder = Arc::new(Mutex::new(Recorder::default()));
+        let providers = resources
+            .iter()
+            .map(|resource| {
+                Arc::new(GatedProvider {
+                    resource: resource.to_string(),
+                    gate: gate.clone(),
+                    recorder: recorder.clone(),
+                }) as Arc<dyn Provider>
+            })
+            .collect();
+        Setup {
+            gate,
+            recorder,
+            providers,
+        }
+    }
+
+    /// Submits a deletion of the file of given name to the engine
+    fn delete(&self, engine: &TransferEngine, provider: usize, name: &str) -> JobId {
+        let provider = &self.providers[provider];
+        let path = provider.initial_path().join(name);
+        engine.submit(Job::delete_file(Location::new(provider.clone(), path)))
+    }
+
+    fn started(&self) -> Vec<String> {
+        self.recorder
+            .lock()
+            .unwrap()
+            .started
+            .iter()
+            .map(|path| path.path_str())
+            .collect()
+    }
+
+    /// Waits until the given number of deletions have started, and makes
+    /// sure no more of them start
+    async fn wait_for_started(&self, count: usize) {
+        tokio::time::timeout(TIMEOUT, async {
+            while self.recorder.lock().unwrap().started.len() < count {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("Jobs haven't started in time");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(self.recorder.lock().unwrap().started.len(), count);
+    }
+}
+
+fn engine(
+    max_jobs: usize,
+    max_jobs_per_provider: usize,
+) -> (TransferEngine, UnboundedReceiver<TransferEvent>) {
+    TransferEngine::new(TransferOptions {
+        max_jobs,
+        max_jobs_per_provider,
+        ..Default::default()
+    })
+}
+
+/// Receives events until all of the given jobs have finished, returns
+/// the states every job has moved through
+async fn wait_for_finished(
+    events: &mut UnboundedReceiver<TransferEvent>,
+    ids: &[JobId],
+) -> HashMap<JobId, Vec<JobState>> {
+    let mut states: HashMap<JobId, Vec<JobState>> = HashMap::new();
+    tokio::time::timeout(TIMEOUT, async {
+        while !ids.iter().all(|id| {
+            states
+                .get(id)
+                .and_then(|s| s.last())
+                .is_some_and(JobState::is_finished)
+        }) {
+            if let Some(TransferEvent::StateChanged { id, state }) = events.recv().await {
+                states.entry(id).or_default().push(state);
+            }
+        }
+    })
+    .await
+    .expect("Jobs haven't finished in time");
+    states
+}
+
+#[tokio::test]
+async fn limits_jobs_running_at_the_same_time() {
+    let setup = Setup::new(&["a", "b"]);
+    let (engine, mut events) = engine(3, 2);
+    let ids: Vec<JobId> = (0..8)
+        .map(|i| setup.delete(&engine, i % 2, &format!("{}.txt", i)))
+        .collect();
+
+    setup.wait_for_started(3).await;
+    setup.gate.add_permits(ids.len());
+    wait_for_finished(&mut events, &ids).await;
+
+    let recorder = setup.recorder.lock().unwrap();
+    assert_eq!(recorder.started.len(), 8);
+    assert_eq!(recorder.peak, 3);
+    assert_eq!(recorder.peak_per_resource["a"], 2);
+    assert!(recorder.peak_per_resource["b"] <= 2);
+}
+
+#[tokio::test]
+async fn limits_jobs_using_the_same_provider() {
+    let setup = Setup::new(&["a", "b"]);
+    let (engine, mut events) = engine(3, 2);
+    let mut ids: Vec<JobId> = (0..4)
+        .map(|i| setup.delete(&engine, 0, &format!("a{}.txt", i)))
+        .collect();
+    ids.extend((0..4).map(|i| setup.delete(&engine, 1, &format!("b{}.txt", i))));
+
+    // The third job waits for its provider, letting a job of the other
+    // provider start before it, but not the jobs of its own provider
+    setup.wait_for_started(3).await;
+    assert_eq!(setup.started(), ["/a0.txt", "/a1.txt", "/b0.txt"]);
+    setup.gate.add_permits(ids.len());
+    wait_for_finished(&mut events, &ids).await;
+
+    let recorder = setup.recorder.lock().unwrap();
+    assert_eq!(recorder.started.len(), 8);
+    assert!(recorder.peak <= 3);
+    assert_eq!(recorder.peak_per_resource["a"], 2);
+    assert!(recorder.peak_per_resource["b"] <= 2);
+}
+
+#[tokio::test]
+async fn starts_jobs_in_the_order_they_were_submitted_in() {
+    let setup = Setup::new(&["a", "b"]);
+    let (engine, mut events) = engine(1, 1);
+    let names = ["e.txt", "b.txt", "d.txt", "a.txt", "c.txt"];
+    let ids: Vec<JobId> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| setup.delete(&engine, i % 2, name))
+        .collect();
+
+    for started in 1..=names.len() {
+        setup.wait_for_started(started).await;
+        setup.gate.add_permits(1);
+    }
+    wait_for_finished(&mut events, &ids).await;
+
+    let expected: Vec<String> = names.iter().map(|name| format!("/{}", name)).collect();
+    assert_eq!(setup.started(), expected);
+}
+
+#[tokio::test]
+async fn moves_jobs_from_queued_through_running_to_completed() {
+    let setup = Setup::new(&["a"]);
+    let (engine, mut events) = engine(1, 1);
+    let first = setup.delete(&engine, 0, "first.txt");
+    let second = setup.delete(&engine, 0, "second.txt");
+
+    setup.wait_for_started(1).await;
+    assert!(matches!(
+        engine.job(first).unwrap().state,
+        JobState::Running
+    ));
+    assert!(matches!(
+        engine.job(second).unwrap().state,
+        JobState::Queued
+    ));
+    setup.gate.add_permits(2);
+    let states = wait_for_finished(&mut events, &[first, second]).await;
+
+    for id in [first, second] {
+        assert!(
+            matches!(
+                states[&id][..],
+                [JobState::Queued, JobState::Running, JobState::Completed]
+            ),
+            "{} moved through {:?}",
+            id,
+            states[&id]
+        );
+    }
+    assert_eq!(engine.summary().done, 2);
+}
+
+/// Provider keeping files in memory, whose files are read slowly, in
+/// chunks of 1 byte every 10ms
+#[derive(Default)]
+struct MemoryProvider {
+    files: Mutex<HashMap<VirtualPath, Vec<u8>>>,
+    /// Number of writes that have started, and of the ones abandoned
+    /// before they finished
+    writes: AtomicUsize,
+    abandoned_writes: Arc<AtomicUsize>,
+    /// Number of reads that have started
+    reads: AtomicUsize,
+    /// Whether files are reported as encrypted with a key of their own
+    encrypted: bool,
+    /// Whether stats are denied, as if the credentials lacked permissions
+    stat_denied: bool,
+    /// Whether sizes are left out of stats
+    sizes_hidden: bool,
+    /// Error writes fail with once their stream has been read
+    write_error: Option<ErrorKind>,
+    /// Whether writes lose their last byte, or flip the bits of their first
+    truncates: bool,
+    corrupts: bool,
+    /// Errors the next reads fail with, one per read
+    read_errors: Mutex<VecDeque<ErrorKind>>,
+}
+
+/// Counts the write it is held by as abandoned, unless it's defused
+struct WriteGuard(Option<Arc<AtomicUsize>>);
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        if let Some(abandoned) = self.0.take() {
+            abandoned.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl MemoryProvider {
+    fn path(&self, name: &str) -> VirtualPath {
+        self.initial_path().join(name)
+    }
+
+    fn insert(&self, name: &str, data: &[u8]) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(self.path(name), data.to_vec());
+    }
+
+    fn get(&self, name: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(&self.path(name)).cloned()
+    }
+}
+
+#[async_trait]
+impl Provider for MemoryProvider {
+    fn provider_name(&self) -> &str {
+        "memory"
+    }
+
+    fn resource_name(&self) -> &str {
+        "memory"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn initial_path(&self) -> VirtualPath {
+        VirtualPath::new("memory", "memory")
+    }
+
+    async fn list(&self, _path: &VirtualPath) -> Result<Vec<Object>, ProviderError> {
+        Err(ProviderError::unsupported("list"))
+    }
+
+    async fn stat(&self, path: &VirtualPath) -> Result<Object, ProviderError> {
+        if self.stat_denied {
+            return Err(ProviderError::new(
+                ErrorKind::PermissionDenied,
+                String::from("Access denied"),
+            ));
+        }
+        match self.files.lock().unwrap().get(path) {
+            Some(data) => Ok(Object {
+                name: path.file_name().unwrap().to_owned(),
+                kind: Kind::File,
+                metadata: Metadata {
+                    size: Some(data.len() as u64).filter(|_| !self.sizes_hidden),
+                    encrypted_with_key: Some(self.encrypted),
+                    ..Default::default()
+                },
+            }),
+            None => Err(ProviderError::new(
+                ErrorKind::NotFound,
+                String::from("No such file"),
+            )),
+        }
+    }
+
+    async fn read(&self, path: &VirtualPath) -> Result<Pin<BoxedByteStream>, ProviderError> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        if let Some(kind) = self.read_errors.lock().unwrap().pop_front() {
+            return Err(ProviderError::new(kind, String::from("Read has failed")));
+        }
+        let data = self
+            .stat(path)
+            .await
+            .map(|_| self.files.lock().unwrap()[path].clone())?;
+        let chunks = futures::stream::iter(data).then(|byte| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(Bytes::from(vec![byte]))
+        });
+        Ok(Box::pin(chunks))
+    }
+
+    async fn write(
+        &self,
+        path: &VirtualPath,
+        mut stream: Pin<BoxedByteStream>,
+    ) -> Result<(), ProviderError> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let mut guard = WriteGuard(Some(self.abandoned_writes.clone()));
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+        guard.0 = None;
+        if let Some(kind) = self.write_error {
+            return Err(ProviderError::new(kind, String::from("Write has failed")));
+        }
+        if self.truncates {
+            data.pop();
+        }
+        if let Some(byte) = data.first_mut().filter(|_| self.corrupts) {
+            *byte = !*byte;
+        }
+        self.files.lock().unwrap().insert(path.clone(), data);
+        Ok(())
+    }
+
+    async fn delete(&self, path: &VirtualPath) -> Result<(), ProviderError> {
+        self.files.lock().unwrap().remove(path);
+        Ok(())
+    }
+}
+
+/// Waits until the given condition holds
+async fn wait_until(condition: impl Fn() -> bool) {
+    tokio::time::timeout(TIMEOUT, async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("Condition hasn't been met in time");
+}
+
+#[tokio::test]
+async fn pausing_abandons_the_transfer_and_resuming_starts_it_over() {
+    let provider = Arc::new(MemoryProvider::default());
+    provider.insert("source.txt", b"0123456789");
+    let (engine, mut events) = engine(1, 1);
+    let location = |name| Location::new(provider.clone(), provider.path(name));
+    let id = engine.submit(Job::copy_file(location("source.txt"), location("copy.txt")));
+
+    wait_until(|| provider.writes.load(Ordering::SeqCst) == 1).await;
+    engine.pause(id);
+    wait_until(|| provider.abandoned_writes.load(Ordering::SeqCst) == 1).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(matches!(engine.job(id).unwrap().state, JobState::Paused));
+    assert_eq!(provider.writes.load(Ordering::SeqCst), 1);
+
+    engine.resume(id);
+    let states = wait_for_finished(&mut events, &[id]).await;
+    assert!(matches!(states[&id].last(), Some(JobState::Completed)));
+    assert_eq!(provider.writes.load(Ordering::SeqCst), 2);
+    assert_eq!(engine.job(id).unwrap().retries, 0);
+    assert_eq!(provider.get("copy.txt").unwrap(), b"0123456789");
+}
+
+#[tokio::test]
+async fn paused_jobs_let_queued_jobs_run() {
+    let provider = Arc::new(MemoryProvider::default());
+    provider.insert("first.txt", b"0123456789");
+    provider.insert("second.txt", b"abc");
+    let (engine, mut events) = engine(1, 1);
+    let location = |name| Location::new(provider.clone(), provider.path(name));
+    let first = engine.submit(Job::copy_file(
+        location("first.txt"),
+        location("first.copy"),
+    ));
+    let second = engine.submit(Job::copy_file(
+        location("second.txt"),
+        location("second.copy"),
+    ));
+
+    wait_until(|| provider.writes.load(Ordering::SeqCst) == 1).await;
+    engine.pause_all();
+    engine.resume(second);
+    let states = wait_for_finished(&mut events, &[second]).await;
+    assert!(matches!(states[&second].last(), Some(JobState::Completed)));
+    assert!(matches!(engine.job(first).unwrap().state, JobState::Paused));
+    assert_eq!(provider.get("second.copy").unwrap(), b"abc");
+
+    engine.resume(first);
+    let states = wait_for_finished(&mut events, &[first]).await;
+    assert!(
+        matches!(
+            states[&first][..],
+            [JobState::Queued, JobState::Running, JobState::Completed]
+        ),
+        "{:?}",
+        states[&first]
+    );
+    assert_eq!(provider.get("first.copy").unwrap(), b"0123456789");
+}
+
+#[tokio::test]
+async fn verifies_encrypted_destinations_by_size_only() {
+    let source = Arc::new(MemoryProvider::default());
+    source.insert("notes.txt", b"notes");
+    let destination = Arc::new(MemoryProvider {
+        encrypted: true,
+        ..Default::default()
+    });
+    let (engine, mut events) = engine(1, 1);
+    let id = engine.submit(Job::copy_file(
+        Location::new(source.clone(), source.path("notes.txt")),
+        Location::new(destination.clone(), destination.path("copy.txt")),
+    ));
+    let states = wait_for_finished(&mut events, &[id]).await;
+
+    assert!(
+        matches!(states[&id].last(), Some(JobState::Completed)),
+        "{:?}",
+        states[&id]
+    );
+    assert_eq!(destination.reads.load(Ordering::SeqCst), 0);
+    assert_eq!(destination.get("copy.txt").unwrap(), b"notes");
+}
+
+#[tokio::test]
+async fn fails_when_the_destination_cant_be_checked_for_conflicts() {
+    let source = Arc::new(MemoryProvider::default());
+    source.insert("notes.txt", b"notes");
+    let destination = Arc::new(MemoryProvider {
+        stat_denied: true,
+        ..Default::default()
+    });
+    destination.insert("copy.txt", b"existing");
+    let (engine, mut events) = TransferEngine::new(TransferOptions {
+        conflict_policy: ConflictPolicy::Skip,
+        ..Default::default()
+    });
+    let id = engine.submit(Job::copy_file(
+        Location::new(source.clone(), source.path("notes.txt")),
+        Location::new(destination.clone(), destination.path("copy.txt")),
+    ));
+    let states = wait_for_finished(&mut events, &[id]).await;
+
+    match states[&id].last() {
+        Some(JobState::Failed(e)) => assert_eq!(e.kind(), ErrorKind::PermissionDenied),
+        state => panic!("{} has finished as {:?}", id, state),
+    }
+    assert_eq!(destination.writes.load(Ordering::SeqCst), 0);
+    assert_eq!(destination.get("copy.txt").unwrap(), b"existing");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn refuses_to_move_a_file_onto_itself() {
+    let dir = std::env::temp_dir().join(format!("versfm-same-file-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    tokio::fs::write(dir.join("notes.txt"), "notes")
+        .await
+        .unwrap();
+    let alias = dir.with_extension("alias");
+    tokio::fs::symlink(&dir, &alias).await.ok();
+
+    let provider: Arc<dyn Provider> = Arc::new(FilesystemProvider::new());
+    let location = |path: &std::path::Path| {
+        let path = format!("file://{}", path.join("notes.txt").display());
+        Location::new(provider.clone(), path.parse().unwrap())
+    };
+    let (engine, mut events) = TransferEngine::new(TransferOptions {
+        conflict_policy: ConflictPolicy::Overwrite,
+        ..Default::default()
+    });
+    let ids = [
+        engine.submit(Job::move_file(location(&dir), location(&dir))),
+        engine.submit(Job::move_file(location(&dir), location(&alias))),
+    ];
+    let states = wait_for_finished(&mut events, &ids).await;
+    let contents = tokio::fs::read_to_string(dir.join("notes.txt")).await;
+    tokio::fs::remove_file(&alias).await.unwrap();
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    for id in ids {
+        match states[&id].last() {
+            Some(JobState::Failed(e)) => assert_eq!(e.code(), "SameFile"),
+            state => panic!("{} has finished as {:?}", id, state),
+        }
+    }
+    assert_eq!(contents.unwrap(), "notes");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn overwrites_files_with_recreated_links() {
+    let dir = std::env::temp_dir().join(format!("versfm-link-{}", std::process::id()));
+    let (from, to) = (dir.join("from"), dir.join("to"));
+    tokio::fs::create_dir_all(&from).await.unwrap();
+    tokio::fs::create_dir_all(&to).await.unwrap();
+    tokio::fs::symlink("target.txt", from.join("link"))
+        .await
+        .unwrap();
+    tokio::fs::write(to.join("link"), "existing").await.unwrap();
+
+    let provider: Arc<dyn Provider> = Arc::new(FilesystemProvider::new());
+    let location = |path: &std::path::Path| {
+        let path = format!("file://{}", path.join("link").display());
+        Location::new(provider.clone(), path.parse().unwrap())
+    };
+    let (engine, mut events) = TransferEngine::new(TransferOptions {
+        link_policy: LinkPolicy::Recreate,
+        conflict_policy: ConflictPolicy::Overwrite,
+        ..Default::default()
+    });
+    let id = engine.submit(Job::copy_file(location(&from), location(&to)));
+    let states = wait_for_finished(&mut events, &[id]).await;
+    let target = tokio::fs::read_link(to.join("link")).await;
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+    assert!(
+        matches!(states[&id].last(), Some(JobState::Completed)),
+        "{:?}",
+        states[&id]
+    );
+    assert_eq!(target.unwrap(), std::path::Path::new("target.txt"));
+}
+
+/// Moves `notes.txt` to a destination with given flaws, returns the
+/// state the move has finished in
+async fn move_to(
+    destination: MemoryProvider,
+    options: TransferOptions,
+) -> (Arc<MemoryProvider>, JobState) {
+    let source = Arc::new(MemoryProvider {
+        sizes_hidden: destination.sizes_hidden,
+        ..Default::default()
+    });
+    source.insert("notes.txt", b"notes");
+    let destination = Arc::new(destination);
+    let (engine, mut events) = TransferEngine::new(options);
+    let id = engine.submit(Job::move_file(
+        Location::new(source.clone(), source.path("notes.txt")),
+        Location::new(destination.clone(), destination.path("moved.txt")),
+    ));
+    let mut states = wait_for_finished(&mut events, &[id]).await;
+    let state = states.remove(&id).unwrap().pop().unwrap();
+    (source, state)
+}
+
+#[tokio::test]
+async fn keeps_the_source_of_moves_that_failed_to_write() {
+    let destination = MemoryProvider {
+        write_error: Some(ErrorKind::PermissionDenied),
+        ..Default::default()
+    };
+    let (source, state) = move_to(destination, TransferOptions::default()).await;
+
+    match state {
+        JobState::Failed(e) => assert_eq!(e.kind(), ErrorKind::PermissionDenied),
+        state => panic!("Move has finished as {:?}", state),
+    }
+    assert_eq!(source.get("notes.txt").unwrap(), b"notes");
+}
+
+#[tokio::test]
+async fn keeps_the_source_of_moves_that_failed_verification() {
+    let flawed = [
+        (
+            MemoryProvider {
+                truncates: true,
+                ..Default::default()
+            },
+            false,
+            "SizeMismatch",
+        ),
+        (
+            MemoryProvider {
+                truncates: true,
+                sizes_hidden: true,
+                ..Default::default()
+            },
+            false,
+            "SizeMismatch",
+        ),
+        (
+            MemoryProvider {
+                corrupts: true,
+                ..Default::default()
+            },
+            true,
+            "ChecksumMismatch",
+        ),
+    ];
+    for (destination, verify_checksums, code) in flawed {
+        let options = TransferOptions {
+            verify_checksums,
+            ..Default::default()
+        };
+        let (source, state) = move_to(destination, options).await;
+
+        match state {
+            JobState::Failed(e) => assert_eq!(e.code(), code),
+            state => panic!("Move has finished as {:?}", state),
+        }
+        assert_eq!(source.get("notes.txt").unwrap(), b"notes");
+    }
+}
+
+#[tokio::test]
+async fn shutting_down_waits_for_cancelled_jobs_to_stop() {
+    let provider = Arc::new(MemoryProvider::default());
+    provider.insert("source.txt", b"0123456789");
+    let (engine, _events) = engine(1, 1);
+    let location = |name| Location::new(provider.clone(), provider.path(name));
+    let running = engine.submit(Job::copy_file(location("source.txt"), location("copy.txt")));
+    let queued = engine.submit(Job::copy_file(
+        location("source.txt"),
+        location("other.txt"),
+    ));
+    wait_until(|| provider.writes.load(Ordering::SeqCst) == 1).await;
+
+    tokio::time::timeout(TIMEOUT, engine.shutdown())
+        .await
+        .expect("Jobs haven't stopped in time");
+
+    for id in [running, queued] {
+        assert!(matches!(engine.job(id).unwrap().state, JobState::Cancelled));
+    }
+    assert_eq!(provider.abandoned_writes.load(Ordering::SeqCst), 1);
+    assert_eq!(provider.writes.load(Ordering::SeqCst), 1);
+    assert!(provider.get("copy.txt").is_none());
+}
+
+/// Copies `notes.txt` from a source whose reads fail with given errors,
+/// returns the source, the retries reported and the copy's final state
+async fn copy_from_flaky(errors: &[ErrorKind]) -> (Arc<MemoryProvider>, Vec<u32>, JobInfo) {
+    let source = Arc::new(MemoryProvider {
+        read_errors: Mutex::new(errors.iter().copied().collect()),
+        ..Default::default()
+    });
+    source.insert("notes.txt", b"notes");
+    let (engine, mut events) = TransferEngine::new(TransferOptions {
+        retry: RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        },
+        ..Default::default()
+    });
+    let id = engine.submit(Job::copy_file(
+        Location::new(source.clone(), source.path("notes.txt")),
+        Location::new(source.clone(), source.path("copy.txt")),
+    ));
+    let mut retries = Vec::new();
+    tokio::time::timeout(TIMEOUT, async {
+        while !engine.job(id).unwrap().state.is_finished() {
+            if let Some(TransferEvent::Retrying { retry, error, .. }) = events.recv().await {
+                assert_eq!(error.kind(), ErrorKind::Transient);
+                retries.push(retry);
+            }
+        }
+    })
+    .await
+    .expect("Copy hasn't finished in time");
+    (source, retries, engine.job(id).unwrap())
+}
+
+#[tokio::test]
+async fn retries_operations_failed_with_transient_errors() {
+    let (source, retries, job) =
+        copy_from_flaky(&[ErrorKind::Transient, ErrorKind::Transient]).await;
+
+    assert!(matches!(job.state, JobState::Completed), "{:?}", job.state);
+    assert_eq!(retries, [1, 2]);
+    assert_eq!(job.retries, 2);
+    assert_eq!(source.get("copy.txt").unwrap(), b"notes");
+}
+
+#[tokio::test]
+async fn doesnt_retry_operations_failed_with_other_errors() {
+    let (source, retries, job) = copy_from_flaky(&[ErrorKind::PermissionDenied]).await;
+
+    match job.state {
+        JobState::Failed(e) => assert_eq!(e.kind(), ErrorKind::PermissionDenied),
+        state => panic!("Copy has finished as {:?}", state),
+    }
+    assert!(retries.is_empty());
+    assert_eq!(job.retries, 0);
+    assert_eq!(source.reads.load(Ordering::SeqCst), 1);
+    assert!(source.get("copy.txt").is_none());
+}
+
+#[tokio::test]
+async fn compares_files_of_the_same_size_by_checksums_if_verifying() {
+    for (existing, verify_checksums, overwritten) in [
+        (&b"other"[..], true, true),
+        (&b"notes"[..], true, false),
+        (&b"other"[..], false, false),
+    ] {
+        let provider = Arc::new(MemoryProvider::default());
+        provider.insert("notes.txt", b"notes");
+        provider.insert("copy.txt", existing);
+        let (engine, mut events) = TransferEngine::new(TransferOptions {
+            conflict_policy: ConflictPolicy::OverwriteIfDifferent,
+            verify_checksums,
+            ..Default::default()
+        });
+        let location = |name| Location::new(provider.clone(), provider.path(name));
+        let id = engine.submit(Job::copy_file(location("notes.txt"), location("copy.txt")));
+        let states = wait_for_finished(&mut events, &[id]).await;
+
+        match (states[&id].last(), overwritten) {
+            (Some(JobState::Completed), true) => {
+                assert_eq!(provider.get("copy.txt").unwrap(), b"notes")
+            }
+            (Some(JobState::Skipped), false) => {
+                assert_eq!(provider.get("copy.txt").unwrap(), existing)
+            }
+            (state, _) => panic!("{} has finished as {:?}", id, state),
+        }
+    }
+}